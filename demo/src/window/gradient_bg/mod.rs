@@ -1,3 +1,4 @@
+use adw::prelude::*;
 use adw::subclass::prelude::*;
 use gtk::gdk;
 use gtk::glib;
@@ -14,6 +15,8 @@ mod imp {
         pub(super) drop_target: TemplateChild<gtk::DropTarget>,
         #[template_child]
         pub(super) gradient_bg: TemplateChild<ori::GradientBg>,
+        #[template_child]
+        pub(super) presets: TemplateChild<gtk::DropDown>,
     }
 
     #[glib::object_subclass]
@@ -39,17 +42,40 @@ mod imp {
             let gradient_bg = self.gradient_bg.to_owned();
 
             let style_manager = adw::StyleManager::default();
-            gradient_bg.set_dark(style_manager.is_dark());
-            gradient_bg.set_theme_colors(hard_coded_theme_colors(style_manager.is_dark()));
 
-            style_manager.connect_dark_notify(clone!(
+            style_manager
+                .bind_property("dark", &gradient_bg, "dark")
+                .sync_create()
+                .build();
+
+            let names: Vec<_> = ori::GradientPreset::all()
+                .iter()
+                .map(|preset| preset.name)
+                .collect();
+            let presets = self.presets.get();
+            presets.set_model(Some(&gtk::StringList::new(&names)));
+
+            let apply_preset = clone!(
+                #[weak]
+                presets,
                 #[weak]
                 gradient_bg,
-                move |style_manager| {
-                    gradient_bg.set_dark(style_manager.is_dark());
-                    gradient_bg.set_theme_colors(hard_coded_theme_colors(style_manager.is_dark()))
+                move || {
+                    if let Some(preset) =
+                        ori::GradientPreset::all().get(presets.selected() as usize)
+                    {
+                        preset.apply(&gradient_bg);
+                    }
                 }
+            );
+
+            apply_preset();
+            gradient_bg.connect_dark_notify(clone!(
+                #[strong]
+                apply_preset,
+                move |_| apply_preset()
             ));
+            presets.connect_selected_notify(move |_| apply_preset());
 
             self.drop_target.connect_drop(clone!(
                 #[weak]
@@ -62,13 +88,15 @@ mod imp {
                         return false;
                     };
 
-                    let pattern_texture = file_list
-                        .files()
-                        .iter()
-                        .filter_map(|file| gdk::Texture::from_file(file).ok())
-                        .next();
+                    let Some(file) = file_list.files().into_iter().next() else {
+                        return false;
+                    };
+
+                    if let Err(e) = gradient_bg.set_pattern_file(&file) {
+                        eprintln!("Can't load the pattern: {e}");
+                        return false;
+                    }
 
-                    gradient_bg.set_pattern(pattern_texture);
                     true
                 }
             ));
@@ -91,11 +119,3 @@ glib::wrapper! {
     pub struct GradientBgPage(ObjectSubclass<imp::GradientBgPage>)
         @extends adw::Bin, gtk::Widget;
 }
-
-fn hard_coded_theme_colors(dark: bool) -> &'static [i32] {
-    if dark {
-        &[0xd6932e, 0xbc40db, 0x4280d7, 0x614ed5]
-    } else {
-        &[0x94dae9, 0x9aeddb, 0x94c3f6, 0xac96f7]
-    }
-}
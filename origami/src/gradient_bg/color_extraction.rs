@@ -0,0 +1,285 @@
+use gtk::gdk;
+use gtk::prelude::*;
+
+use super::color_space::Oklab;
+use super::software_gradient::Color;
+
+/// Number of colors in a freeform Telegram gradient
+const COLOR_COUNT: usize = 4;
+
+/// Maximum number of pixels sampled on each axis
+const SAMPLE_SIZE: usize = 64;
+
+const MAX_ITERATIONS: usize = 16;
+
+/// Pixels that are more transparent are skipped
+const MIN_ALPHA: u8 = 128;
+
+/// Picks four dominant colors of the image for a freeform gradient
+///
+/// Pixels are clustered with k-means in the perceptual Oklab color space,
+/// the clusters are seeded with the most distant colors, so the result is well separated.
+/// There is no randomness, the same image always gives the same colors.
+/// Colors are sorted from the most to the least common one
+/// and can be passed to [GradientBg::set_theme_colors](super::GradientBg::set_theme_colors).
+///
+/// Images without opaque pixels give black colors.
+pub fn extract_theme_colors(texture: &impl IsA<gdk::Texture>) -> [i32; 4] {
+    let samples = sample_pixels(texture.as_ref());
+
+    if samples.is_empty() {
+        return [0; COLOR_COUNT];
+    }
+
+    let mut centers = initial_centers(&samples);
+    let mut assignments = vec![0; samples.len()];
+
+    for _ in 0..MAX_ITERATIONS {
+        let mut changed = false;
+
+        for (sample, assignment) in samples.iter().zip(&mut assignments) {
+            let nearest = nearest_center(&centers, *sample);
+            if nearest != *assignment {
+                *assignment = nearest;
+                changed = true;
+            }
+        }
+
+        let mut sums = [(0.0, 0.0, 0.0, 0usize); COLOR_COUNT];
+        for (sample, &assignment) in samples.iter().zip(&assignments) {
+            let sum = &mut sums[assignment];
+            sum.0 += sample.l;
+            sum.1 += sample.a;
+            sum.2 += sample.b;
+            sum.3 += 1;
+        }
+
+        // Empty clusters keep their previous centers
+        for (center, (l, a, b, count)) in centers.iter_mut().zip(sums) {
+            if count > 0 {
+                let count = count as f32;
+                *center = Oklab {
+                    l: l / count,
+                    a: a / count,
+                    b: b / count,
+                };
+            }
+        }
+
+        if !changed {
+            break;
+        }
+    }
+
+    let mut sizes = [0usize; COLOR_COUNT];
+    for &assignment in &assignments {
+        sizes[assignment] += 1;
+    }
+
+    let mut order: [usize; COLOR_COUNT] = std::array::from_fn(|i| i);
+    // The sort is stable, so equal clusters keep the seeding order
+    order.sort_by_key(|&i| std::cmp::Reverse(sizes[i]));
+
+    order.map(|i| centers[i].to_color().to_int_rgb())
+}
+
+/// Returns opaque pixels of the image on a grid of at most 64x64 pixels
+fn sample_pixels(texture: &gdk::Texture) -> Vec<Oklab> {
+    let (width, height) = (texture.width() as usize, texture.height() as usize);
+
+    if width == 0 || height == 0 {
+        return Vec::new();
+    }
+
+    let mut downloader = gdk::TextureDownloader::new(texture);
+    downloader.set_format(gdk::MemoryFormat::R8g8b8a8);
+    let (bytes, stride) = downloader.download_bytes();
+
+    let step_x = width.div_ceil(SAMPLE_SIZE);
+    let step_y = height.div_ceil(SAMPLE_SIZE);
+
+    (0..height)
+        .step_by(step_y)
+        .flat_map(|y| (0..width).step_by(step_x).map(move |x| (x, y)))
+        .filter_map(|(x, y)| {
+            let offset = y * stride + 4 * x;
+            let pixel = &bytes[offset..offset + 4];
+
+            (pixel[3] >= MIN_ALPHA).then(|| {
+                Oklab::from_color(Color::new(
+                    pixel[0] as f32 / 255.0,
+                    pixel[1] as f32 / 255.0,
+                    pixel[2] as f32 / 255.0,
+                ))
+            })
+        })
+        .collect()
+}
+
+/// Seeds clusters with the sample closest to the mean color,
+/// then with the samples farthest from the already chosen ones
+fn initial_centers(samples: &[Oklab]) -> [Oklab; COLOR_COUNT] {
+    let count = samples.len() as f32;
+    let mean = samples.iter().fold(
+        Oklab {
+            l: 0.0,
+            a: 0.0,
+            b: 0.0,
+        },
+        |sum, sample| Oklab {
+            l: sum.l + sample.l / count,
+            a: sum.a + sample.a / count,
+            b: sum.b + sample.b / count,
+        },
+    );
+
+    let mut centers =
+        [closest_sample(samples, |sample| sample.distance_squared(mean)); COLOR_COUNT];
+
+    for i in 1..COLOR_COUNT {
+        centers[i] = closest_sample(samples, |sample| {
+            -centers[..i]
+                .iter()
+                .map(|center| sample.distance_squared(*center))
+                .fold(f32::INFINITY, f32::min)
+        });
+    }
+
+    centers
+}
+
+/// Returns the first sample with the smallest key
+fn closest_sample(samples: &[Oklab], key: impl Fn(Oklab) -> f32) -> Oklab {
+    samples
+        .iter()
+        .copied()
+        .map(|sample| (key(sample), sample))
+        .fold(None, |best: Option<(f32, Oklab)>, current| match best {
+            Some(best) if best.0 <= current.0 => Some(best),
+            _ => Some(current),
+        })
+        .map(|(_, sample)| sample)
+        .unwrap()
+}
+
+fn nearest_center(centers: &[Oklab], sample: Oklab) -> usize {
+    centers
+        .iter()
+        .enumerate()
+        .fold((0, f32::INFINITY), |best, (i, center)| {
+            let distance = sample.distance_squared(*center);
+            if distance < best.1 {
+                (i, distance)
+            } else {
+                best
+            }
+        })
+        .0
+}
+
+#[cfg(test)]
+mod tests {
+    use gtk::glib;
+
+    use super::*;
+
+    /// Creates an opaque texture, `color_at` returns an int color of the pixel
+    fn texture(
+        width: usize,
+        height: usize,
+        color_at: impl Fn(usize, usize) -> i32,
+    ) -> gdk::Texture {
+        texture_with_alpha(width, height, |x, y| (color_at(x, y), 255))
+    }
+
+    fn texture_with_alpha(
+        width: usize,
+        height: usize,
+        pixel_at: impl Fn(usize, usize) -> (i32, u8),
+    ) -> gdk::Texture {
+        let mut data = Vec::with_capacity(4 * width * height);
+        for y in 0..height {
+            for x in 0..width {
+                let (color, alpha) = pixel_at(x, y);
+                data.extend([(color >> 16) as u8, (color >> 8) as u8, color as u8, alpha]);
+            }
+        }
+
+        gdk::MemoryTexture::new(
+            width as i32,
+            height as i32,
+            gdk::MemoryFormat::R8g8b8a8,
+            &glib::Bytes::from_owned(data),
+            4 * width,
+        )
+        .upcast()
+    }
+
+    fn assert_colors_eq(actual: [i32; 4], expected: [i32; 4]) {
+        for (actual, expected) in actual.into_iter().zip(expected) {
+            for shift in [16, 8, 0] {
+                let actual_channel = (actual >> shift) & 0xff;
+                let expected_channel = (expected >> shift) & 0xff;
+                assert!(
+                    actual_channel.abs_diff(expected_channel) <= 1,
+                    "expected {expected:06x}, got {actual:06x}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn quadrants_sorted_by_area() {
+        // Quadrants split at x = 5 and y = 6 have 30, 18, 10 and 6 pixels
+        let texture = texture(8, 8, |x, y| match (x < 5, y < 6) {
+            (true, true) => 0xff0000,
+            (false, true) => 0x00ff00,
+            (true, false) => 0x0000ff,
+            (false, false) => 0xffff00,
+        });
+
+        let colors = extract_theme_colors(&texture);
+
+        assert_colors_eq(colors, [0xff0000, 0x00ff00, 0x0000ff, 0xffff00]);
+        assert_eq!(colors, extract_theme_colors(&texture));
+    }
+
+    #[test]
+    fn large_image_is_sampled() {
+        // Every 5th column and 4th row is sampled, so the quadrants have 1260, 840, 540
+        // and 360 samples
+        let texture = texture(300, 200, |x, y| match (x < 180, y < 140) {
+            (true, true) => 0x202040,
+            (false, true) => 0xe0c080,
+            (true, false) => 0x40a060,
+            (false, false) => 0x9030b0,
+        });
+
+        assert_colors_eq(
+            extract_theme_colors(&texture),
+            [0x202040, 0xe0c080, 0x40a060, 0x9030b0],
+        );
+    }
+
+    #[test]
+    fn single_color() {
+        let texture = texture(16, 16, |_, _| 0x336699);
+
+        assert_colors_eq(extract_theme_colors(&texture), [0x336699; 4]);
+    }
+
+    #[test]
+    fn transparent_pixels_are_skipped() {
+        let transparent = texture_with_alpha(16, 16, |_, _| (0xffffff, 0));
+        assert_eq!(extract_theme_colors(&transparent), [0; 4]);
+
+        let half_transparent = texture_with_alpha(16, 16, |x, _| {
+            if x < 8 {
+                (0xffffff, 0)
+            } else {
+                (0x336699, 255)
+            }
+        });
+        assert_colors_eq(extract_theme_colors(&half_transparent), [0x336699; 4]);
+    }
+}
@@ -0,0 +1,125 @@
+//! Conversions between sRGB, linear light and [Oklab](https://bottosson.github.io/posts/oklab/)
+
+use gtk::glib;
+
+use super::software_gradient::Color;
+
+/// Color space in which gradient colors are mixed
+///
+/// Telegram mixes colors in sRGB, it's the default.
+/// Other spaces avoid muddy midpoints between saturated colors,
+/// but they aren't supported by the GPU path, so the gradient is rendered on the CPU.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, glib::Enum)]
+#[enum_type(name = "OriColorSpace")]
+pub enum ColorSpace {
+    /// Gamma-encoded sRGB like in Telegram
+    #[default]
+    Srgb,
+    /// Linear light, mixes colors like physical light does
+    LinearRgb,
+    /// Perceptual Oklab space, keeps midpoints bright and saturated
+    Oklab,
+}
+
+impl ColorSpace {
+    /// Converts a color to channels that can be mixed linearly
+    pub(super) fn encode(self, color: Color) -> [f32; 3] {
+        match self {
+            Self::Srgb => color.to_array(),
+            Self::LinearRgb => color.to_array().map(srgb_to_linear),
+            Self::Oklab => {
+                let oklab = Oklab::from_color(color);
+                [oklab.l, oklab.a, oklab.b]
+            }
+        }
+    }
+
+    /// Converts mixed channels back to a sRGB color
+    pub(super) fn decode(self, [first, second, third]: [f32; 3]) -> Color {
+        match self {
+            Self::Srgb => Color::new(first, second, third),
+            Self::LinearRgb => Color::new(
+                linear_to_srgb(first),
+                linear_to_srgb(second),
+                linear_to_srgb(third),
+            ),
+            Self::Oklab => Oklab {
+                l: first,
+                a: second,
+                b: third,
+            }
+            .to_color(),
+        }
+    }
+
+    pub(super) fn interpolate(self, from: Color, to: Color, factor: f32) -> Color {
+        let from = self.encode(from);
+        let to = self.encode(to);
+
+        self.decode(std::array::from_fn(|i| {
+            from[i] * (1.0 - factor) + to[i] * factor
+        }))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(super) struct Oklab {
+    pub(super) l: f32,
+    pub(super) a: f32,
+    pub(super) b: f32,
+}
+
+impl Oklab {
+    pub(super) fn from_color(color: Color) -> Self {
+        let [r, g, b] = color.to_array().map(srgb_to_linear);
+
+        let l = (0.412_221_46 * r + 0.536_332_55 * g + 0.051_445_995 * b).cbrt();
+        let m = (0.211_903_5 * r + 0.680_699_5 * g + 0.107_396_96 * b).cbrt();
+        let s = (0.088_302_46 * r + 0.281_718_85 * g + 0.629_978_7 * b).cbrt();
+
+        Self {
+            l: 0.210_454_26 * l + 0.793_617_8 * m - 0.004_072_047 * s,
+            a: 1.977_998_5 * l - 2.428_592_2 * m + 0.450_593_7 * s,
+            b: 0.025_904_037 * l + 0.782_771_77 * m - 0.808_675_77 * s,
+        }
+    }
+
+    /// Converts back to sRGB, colors outside of the gamut are clamped
+    pub(super) fn to_color(self) -> Color {
+        let l = self.l + 0.396_337_78 * self.a + 0.215_803_76 * self.b;
+        let m = self.l - 0.105_561_346 * self.a - 0.063_854_17 * self.b;
+        let s = self.l - 0.089_484_18 * self.a - 1.291_485_5 * self.b;
+
+        let (l, m, s) = (l * l * l, m * m * m, s * s * s);
+
+        let [r, g, b] = [
+            4.076_741_7 * l - 3.307_711_6 * m + 0.230_969_94 * s,
+            -1.268_438 * l + 2.609_757_4 * m - 0.341_319_38 * s,
+            -0.004_196_086_3 * l - 0.703_418_6 * m + 1.707_614_7 * s,
+        ]
+        .map(|channel| linear_to_srgb(channel).clamp(0.0, 1.0));
+
+        Color::new(r, g, b)
+    }
+
+    pub(super) fn distance_squared(self, other: Self) -> f32 {
+        let (dl, da, db) = (self.l - other.l, self.a - other.a, self.b - other.b);
+        dl * dl + da * da + db * db
+    }
+}
+
+pub(super) fn srgb_to_linear(channel: f32) -> f32 {
+    if channel <= 0.04045 {
+        channel / 12.92
+    } else {
+        ((channel + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+pub(super) fn linear_to_srgb(channel: f32) -> f32 {
+    if channel <= 0.003_130_8 {
+        channel * 12.92
+    } else {
+        1.055 * channel.max(0.0).powf(1.0 / 2.4) - 0.055
+    }
+}
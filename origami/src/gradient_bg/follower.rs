@@ -0,0 +1,140 @@
+use std::cell::RefCell;
+
+use gtk::glib;
+use gtk::prelude::*;
+
+use super::format_colors;
+use super::parse_colors;
+use super::AnimatedTexture;
+use super::ColorSpace;
+use super::GradientBg;
+use super::GradientRenderer;
+
+/// Gradient state of [GradientFill](crate::GradientFill) and [GradientPaintable](super::GradientPaintable)
+///
+/// Keeps the renderer with its texture and the connection to a followed [GradientBg],
+/// setters return `true` on changes, so the owner can redraw and notify.
+#[derive(Default)]
+pub(crate) struct FollowedGradient {
+    pub(crate) renderer: RefCell<GradientRenderer>,
+    pub(crate) texture: AnimatedTexture,
+    /// Bindings to the followed [GradientBg]
+    bindings: RefCell<Vec<glib::Binding>>,
+    /// Handler copying colors and custom points of the followed [GradientBg]
+    colors_handler: RefCell<Option<(glib::WeakRef<GradientBg>, glib::SignalHandlerId)>>,
+}
+
+impl FollowedGradient {
+    pub(crate) fn colors(&self) -> Vec<String> {
+        format_colors(&self.renderer.borrow())
+    }
+
+    /// Accepts any color format supported by [gdk::RGBA::parse](gtk::gdk::RGBA::parse)
+    pub(crate) fn set_colors(&self, colors: &[String]) -> bool {
+        match parse_colors(colors) {
+            Ok(colors) => self.set_theme_colors(&colors),
+            Err(e) => {
+                log::warn!("can't parse gradient colors: {e}");
+                false
+            }
+        }
+    }
+
+    /// Invalid colors are ignored, see [GradientRenderer::set_colors]
+    pub(crate) fn set_theme_colors(&self, colors: &[i32]) -> bool {
+        match self.renderer.borrow_mut().set_colors(colors) {
+            Ok(()) => true,
+            Err(e) => {
+                log::warn!("{e}");
+                false
+            }
+        }
+    }
+
+    /// Copies colors and custom points, they are checked together by the source
+    pub(crate) fn set_gradient_from(&self, source: &GradientRenderer) -> bool {
+        if source.same_gradient(&self.renderer.borrow()) {
+            return false;
+        }
+
+        self.renderer.borrow_mut().set_gradient_from(source);
+        true
+    }
+
+    pub(crate) fn phase(&self) -> u32 {
+        self.renderer.borrow().phase()
+    }
+
+    pub(crate) fn set_phase(&self, phase: u32) {
+        self.renderer.borrow_mut().set_phase(phase);
+    }
+
+    pub(crate) fn progress(&self) -> f32 {
+        self.renderer.borrow().progress()
+    }
+
+    pub(crate) fn set_progress(&self, progress: f32) {
+        self.renderer.borrow_mut().set_progress(progress);
+    }
+
+    pub(crate) fn rotation(&self) -> i32 {
+        self.renderer.borrow().rotation()
+    }
+
+    pub(crate) fn set_rotation(&self, rotation: i32) {
+        self.renderer.borrow_mut().set_rotation(rotation);
+    }
+
+    pub(crate) fn color_space(&self) -> ColorSpace {
+        self.renderer.borrow().color_space()
+    }
+
+    pub(crate) fn set_color_space(&self, color_space: ColorSpace) {
+        self.renderer.borrow_mut().set_color_space(color_space);
+    }
+
+    /// Binds animation properties of `target` to the [GradientBg]
+    ///
+    /// `set_gradient` is called with the renderer of the widget right away
+    /// and every time its colors or custom points change.
+    /// Bindings to the previously followed widget are removed.
+    pub(crate) fn follow(
+        &self,
+        target: &impl IsA<glib::Object>,
+        gradient_bg: &GradientBg,
+        set_gradient: impl Fn(&GradientRenderer) + 'static,
+    ) {
+        self.unfollow();
+
+        set_gradient(&gradient_bg.renderer());
+        let handler = gradient_bg
+            .connect_colors_notify(move |gradient_bg| set_gradient(&gradient_bg.renderer()));
+        self.colors_handler
+            .replace(Some((gradient_bg.downgrade(), handler)));
+
+        let bindings = ["phase", "progress", "rotation", "color-space"]
+            .into_iter()
+            .map(|property| {
+                gradient_bg
+                    .bind_property(property, target, property)
+                    .sync_create()
+                    .build()
+            })
+            .collect();
+
+        self.bindings.replace(bindings);
+    }
+
+    /// Removes bindings and handlers of the followed [GradientBg]
+    pub(crate) fn unfollow(&self) {
+        for binding in self.bindings.take() {
+            binding.unbind();
+        }
+
+        if let Some((gradient_bg, handler)) = self.colors_handler.take() {
+            if let Some(gradient_bg) = gradient_bg.upgrade() {
+                gradient_bg.disconnect(handler);
+            }
+        }
+    }
+}
@@ -0,0 +1,63 @@
+// GPU version of `generate_gradient` from software_gradient.rs
+uniform int u_color_count;
+
+uniform vec3 u_color1;
+uniform vec3 u_color2;
+uniform vec3 u_color3;
+uniform vec3 u_color4;
+
+uniform vec2 u_position1;
+uniform vec2 u_position2;
+uniform vec2 u_position3;
+uniform vec2 u_position4;
+
+void accumulate(vec2 pixel, vec3 color, vec2 position, inout vec3 sum, inout float distance_sum) {
+    float distance = max(0.92 - distance(pixel, position), 0.0);
+    distance = distance * distance * distance;
+
+    distance_sum += distance;
+    sum += distance * color;
+}
+
+// Interleaved gradient noise for dithering, the shader draws at the output size,
+// so it can use noise instead of the Bayer matrix of `quantize` in software_gradient.rs
+float dither_noise(vec2 position) {
+    return fract(52.9829189 * fract(dot(position, vec2(0.06711056, 0.00583715))));
+}
+
+void mainImage(out vec4 fragColor,
+    in vec2 fragCoord,
+    in vec2 resolution,
+    in vec2 uv) {
+    vec2 center_distance = fragCoord / resolution - 0.5;
+
+    float swirl_factor = 0.35 * length(center_distance);
+    float theta = swirl_factor * swirl_factor * 0.8 * 8.0;
+
+    float sin_theta = sin(theta);
+    float cos_theta = cos(theta);
+
+    vec2 pixel = clamp(vec2(
+        0.5 + center_distance.x * cos_theta - center_distance.y * sin_theta,
+        0.5 + center_distance.x * sin_theta + center_distance.y * cos_theta
+    ), 0.0, 1.0);
+
+    vec3 sum = vec3(0.0);
+    float distance_sum = 0.0;
+
+    accumulate(pixel, u_color1, u_position1, sum, distance_sum);
+    if (u_color_count > 1) {
+        accumulate(pixel, u_color2, u_position2, sum, distance_sum);
+    }
+    if (u_color_count > 2) {
+        accumulate(pixel, u_color3, u_position3, sum, distance_sum);
+    }
+    if (u_color_count > 3) {
+        accumulate(pixel, u_color4, u_position4, sum, distance_sum);
+    }
+
+    // Spread the rounding error of 8 bit framebuffers to avoid banding
+    vec3 noise = vec3((dither_noise(gl_FragCoord.xy) - 0.5) / 255.0);
+
+    fragColor = vec4(sum / distance_sum + noise, 1.0);
+}
@@ -1,4 +1,14 @@
+mod color_extraction;
+mod color_space;
+mod follower;
+mod paintable;
+mod pattern;
+mod preset;
+mod renderer;
+mod service_colors;
 mod software_gradient;
+mod texture_cache;
+mod wallpaper_spec;
 
 use std::cell::Cell;
 use std::cell::OnceCell;
@@ -8,24 +18,170 @@ use adw::prelude::*;
 use adw::subclass::prelude::*;
 use glib::clone;
 use gtk::gdk;
+use gtk::gio;
 use gtk::glib;
 use gtk::graphene;
+use gtk::gsk;
+
+pub use color_extraction::extract_theme_colors;
+pub use color_space::ColorSpace;
+pub use paintable::GradientPaintable;
+pub use preset::GradientPreset;
+pub use renderer::GradientRenderer;
+pub use service_colors::contrast_ratio;
+pub use service_colors::ServiceColors;
+pub use wallpaper_spec::WallpaperSettings;
+pub use wallpaper_spec::WallpaperSpec;
+pub use wallpaper_spec::WallpaperSpecError;
+
+pub(crate) use follower::FollowedGradient;
+pub(crate) use renderer::PHASE_COUNT;
+pub(crate) use texture_cache::AnimatedTexture;
+
+const GRADIENT_SHADER: &[u8] = include_bytes!("gradient_shader.frag");
+
+/// Pattern intensity used by Telegram when a wallpaper doesn't specify it
+const DEFAULT_INTENSITY: i32 = 50;
+
+/// Maximum number of colors in a Telegram wallpaper
+pub(crate) const MAX_COLORS: usize = 4;
+
+/// Duration of the animation in milliseconds
+const DEFAULT_DURATION: u32 = 200;
+
+/// Size of gradient textures that are upscaled to the widget
+///
+/// Even with 4x4 the upscaled result looks good, the swirl is cached, so 64x64 is cheap enough
+const GRADIENT_TEXTURE_SIZE: u32 = 64;
+
+/// Duration of the cross-fade between wallpapers in milliseconds
+const DEFAULT_TRANSITION_DURATION: u32 = 300;
+
+/// Size of a pattern tile relative to the pattern image
+const DEFAULT_PATTERN_SCALE: f32 = 0.3;
+
+/// Blur radius of photo wallpapers with blur enabled
+const PHOTO_BLUR_RADIUS: f32 = 12.0;
+
+/// Opacity of the black layer over photo wallpapers in dark mode
+const DEFAULT_DIM: f32 = 0.3;
+
+/// Maximum size of the photo sample used to compute average colors
+const PHOTO_SAMPLE_SIZE: u32 = 64;
+
+/// Zoom of the wallpaper in motion mode, it leaves room for the offset
+const MOTION_SCALE: f32 = 1.1;
+
+/// Part of the remaining distance to the target offset passed every frame
+const MOTION_SMOOTHING: f32 = 0.15;
 
 mod imp {
     use super::*;
 
-    #[derive(Default)]
+    #[derive(Default, glib::Properties)]
+    #[properties(wrapper_type = super::GradientBg)]
     pub struct GradientBg {
-        pub(super) gradient_texture: RefCell<Option<gdk::MemoryTexture>>,
+        pub(super) shader: RefCell<Option<gsk::GLShader>>,
+
+        #[property(
+            name = "pattern",
+            get = Self::pattern_texture,
+            set = Self::set_pattern,
+            nullable,
+            type = Option<gdk::Texture>
+        )]
+        pub(super) pattern: RefCell<Option<pattern::Pattern>>,
+        #[property(get, set = Self::set_pattern_scale, minimum = 0.01, default = DEFAULT_PATTERN_SCALE)]
+        pub(super) pattern_scale: Cell<f32>,
 
-        pub(super) pattern: RefCell<Option<gdk::Texture>>,
+        #[property(get, set = Self::set_photo, nullable)]
+        pub(super) photo: RefCell<Option<gdk::Texture>>,
+        #[property(get, set = Self::set_blur)]
+        pub(super) blur: Cell<bool>,
+        #[property(get, set = Self::set_dim, minimum = 0.0, maximum = 1.0, default = DEFAULT_DIM)]
+        pub(super) dim: Cell<f32>,
 
-        pub(super) animation: OnceCell<adw::Animation>,
-        pub(super) progress: Cell<f32>,
-        pub(super) phase: Cell<u32>,
+        #[property(
+            name = "duration",
+            get = Self::duration,
+            set = Self::set_duration,
+            default = DEFAULT_DURATION,
+            type = u32
+        )]
+        #[property(
+            name = "easing",
+            get = Self::easing,
+            set = Self::set_easing,
+            type = adw::Easing,
+            builder(adw::Easing::EaseInOutQuad)
+        )]
+        pub(super) animation: OnceCell<adw::TimedAnimation>,
+        #[property(
+            name = "transition-duration",
+            get = Self::transition_duration,
+            set = Self::set_transition_duration,
+            default = DEFAULT_TRANSITION_DURATION,
+            type = u32
+        )]
+        pub(super) transition: OnceCell<adw::TimedAnimation>,
+        /// Wallpaper before the last change, it's faded out during the transition
+        pub(super) transition_texture: RefCell<Option<gdk::Texture>>,
+        /// Phase at the start of the current animation
+        pub(super) animation_start_phase: Cell<u32>,
+        /// Steps requested while the animation was playing, negative steps go backwards
+        pub(super) queued_steps: Cell<i32>,
+        #[property(get, set = Self::set_autoplay)]
+        pub(super) autoplay: Cell<bool>,
+        #[property(get, set)]
+        pub(super) reverse: Cell<bool>,
+        #[property(name = "colors", get = Self::colors, set = Self::set_colors, type = Vec<String>)]
+        #[property(
+            name = "phase",
+            get = Self::phase,
+            set = Self::set_phase,
+            maximum = renderer::PHASE_COUNT - 1,
+            type = u32
+        )]
+        #[property(
+            name = "progress",
+            get = Self::progress,
+            set = Self::set_progress,
+            minimum = 0.0,
+            maximum = 1.0,
+            type = f32
+        )]
+        #[property(name = "rotation", get = Self::rotation, set = Self::set_rotation, type = i32)]
+        #[property(
+            name = "color-space",
+            get = Self::color_space,
+            set = Self::set_color_space,
+            type = ColorSpace,
+            builder(ColorSpace::Srgb)
+        )]
+        pub(super) renderer: RefCell<GradientRenderer>,
+        pub(super) gradient_texture: AnimatedTexture,
+        /// Average alpha of the pattern, it's computed on the first request of the average color
+        pub(super) pattern_coverage: Cell<Option<f32>>,
+        pub(super) photo_sample: RefCell<Option<service_colors::TextureSample>>,
 
+        #[property(get, set = Self::set_dark)]
         pub(super) dark: Cell<bool>,
-        pub(super) colors: RefCell<Vec<software_gradient::Color>>,
+        #[property(
+            get,
+            set = Self::set_intensity,
+            minimum = -100,
+            maximum = 100,
+            default = DEFAULT_INTENSITY
+        )]
+        pub(super) intensity: Cell<i32>,
+
+        #[property(get, set = Self::set_motion)]
+        pub(super) motion: Cell<bool>,
+        /// Current and target offsets of the wallpaper, from -1 to 1 on each axis
+        pub(super) motion_offset: Cell<(f32, f32)>,
+        pub(super) motion_target: Cell<(f32, f32)>,
+        pub(super) motion_tick: RefCell<Option<gtk::TickCallbackId>>,
+        pub(super) motion_adjustment: RefCell<Option<(gtk::Adjustment, glib::SignalHandlerId)>>,
     }
 
     #[glib::object_subclass]
@@ -36,9 +192,25 @@ mod imp {
     }
 
     impl ObjectImpl for GradientBg {
+        fn properties() -> &'static [glib::ParamSpec] {
+            Self::derived_properties()
+        }
+
+        fn property(&self, id: usize, pspec: &glib::ParamSpec) -> glib::Value {
+            self.derived_property(id, pspec)
+        }
+
+        fn set_property(&self, id: usize, value: &glib::Value, pspec: &glib::ParamSpec) {
+            self.derived_set_property(id, value, pspec)
+        }
+
         fn constructed(&self) {
             self.parent_constructed();
 
+            self.intensity.set(DEFAULT_INTENSITY);
+            self.pattern_scale.set(DEFAULT_PATTERN_SCALE);
+            self.dim.set(DEFAULT_DIM);
+
             let obj = self.obj();
 
             let style_manager = adw::StyleManager::default();
@@ -60,16 +232,23 @@ mod imp {
             let target = adw::CallbackAnimationTarget::new(clone!(
                 #[weak]
                 obj,
-                move |progress| {
+                move |value| {
                     let imp = obj.imp();
-                    imp.gradient_texture.take();
-                    let progress = progress as f32;
-                    if progress >= 1.0 {
-                        imp.progress.set(0.0);
-                        imp.phase.set((imp.phase.get() + 1) % 8);
-                    } else {
-                        imp.progress.set(progress)
+
+                    // The value is the number of steps passed since the start
+                    let steps = value.floor();
+                    let phase = imp.animation_start_phase.get() + steps as u32;
+
+                    let mut renderer = imp.renderer.borrow_mut();
+                    let phase_changed = renderer.phase() != phase % renderer::PHASE_COUNT;
+                    renderer.set_phase(phase);
+                    renderer.set_progress((value - steps) as f32);
+                    drop(renderer);
+
+                    if phase_changed {
+                        obj.notify_phase();
                     }
+                    obj.notify_progress();
                     obj.queue_draw();
                 }
             ));
@@ -78,17 +257,121 @@ mod imp {
                 .widget(&*obj)
                 .value_from(0.0)
                 .value_to(1.0)
-                .duration(200)
+                .duration(DEFAULT_DURATION)
                 .target(&target)
                 .easing(adw::Easing::EaseInOutQuad)
-                .build()
-                .upcast();
+                .build();
+
+            animation.connect_done(clone!(
+                #[weak]
+                obj,
+                move |_| {
+                    let imp = obj.imp();
+                    let queued_steps = imp.queued_steps.replace(0);
+
+                    if queued_steps != 0 {
+                        imp.start_animation(queued_steps);
+                    } else if imp.autoplay_allowed() {
+                        imp.start_animation(imp.direction());
+                    }
+                }
+            ));
 
             self.animation.set(animation).unwrap();
+
+            let transition_target = adw::CallbackAnimationTarget::new(clone!(
+                #[weak]
+                obj,
+                move |_| obj.queue_draw()
+            ));
+
+            let transition = adw::TimedAnimation::builder()
+                .widget(&*obj)
+                .value_from(0.0)
+                .value_to(1.0)
+                .duration(DEFAULT_TRANSITION_DURATION)
+                .target(&transition_target)
+                .easing(adw::Easing::EaseOutQuad)
+                .build();
+
+            transition.connect_done(clone!(
+                #[weak]
+                obj,
+                move |_| {
+                    obj.imp().transition_texture.take();
+                    obj.queue_draw();
+                }
+            ));
+
+            self.transition.set(transition).unwrap();
+
+            let motion_controller = gtk::EventControllerMotion::new();
+
+            motion_controller.connect_motion(clone!(
+                #[weak]
+                obj,
+                move |_, x, y| {
+                    // The scroll offset drives the motion when an adjustment is attached
+                    if obj.imp().motion_adjustment.borrow().is_some() {
+                        return;
+                    }
+
+                    let (width, height) = (obj.width() as f32, obj.height() as f32);
+                    if width > 0.0 && height > 0.0 {
+                        obj.imp().set_motion_target(
+                            x as f32 / width * 2.0 - 1.0,
+                            y as f32 / height * 2.0 - 1.0,
+                        );
+                    }
+                }
+            ));
+
+            motion_controller.connect_leave(clone!(
+                #[weak]
+                obj,
+                move |_| {
+                    if obj.imp().motion_adjustment.borrow().is_none() {
+                        obj.imp().set_motion_target(0.0, 0.0);
+                    }
+                }
+            ));
+
+            obj.add_controller(motion_controller);
+        }
+
+        fn dispose(&self) {
+            if let Some((adjustment, handler)) = self.motion_adjustment.take() {
+                adjustment.disconnect(handler);
+            }
         }
     }
 
     impl WidgetImpl for GradientBg {
+        fn map(&self) {
+            self.parent_map();
+
+            // Autoplay stops when the widget is hidden
+            if self.autoplay_allowed() && !self.is_animating() {
+                self.start_animation(self.direction());
+            }
+        }
+
+        fn unmap(&self) {
+            self.transition.get().unwrap().skip();
+            self.parent_unmap();
+        }
+
+        fn realize(&self) {
+            self.parent_realize();
+            self.ensure_shader();
+        }
+
+        fn unrealize(&self) {
+            // The shader is compiled for the renderer of the current native
+            self.shader.take();
+            self.parent_unrealize();
+        }
+
         fn snapshot(&self, snapshot: &gtk::Snapshot) {
             let widget = self.obj();
 
@@ -109,107 +392,766 @@ mod imp {
 
             let bounds = graphene::Rect::new(0.0, 0.0, width, height);
 
-            self.snapshot_gradient(snapshot, &bounds);
+            if self.motion_enabled() {
+                let (offset_x, offset_y) = self.motion_offset.get();
+
+                // Half of the extra size on each side
+                let extra_width = (MOTION_SCALE - 1.0) * width * 0.5;
+                let extra_height = (MOTION_SCALE - 1.0) * height * 0.5;
+
+                snapshot.push_clip(&bounds);
+                snapshot.save();
+                snapshot.translate(&graphene::Point::new(
+                    width * 0.5 - offset_x * extra_width,
+                    height * 0.5 - offset_y * extra_height,
+                ));
+                snapshot.scale(MOTION_SCALE, MOTION_SCALE);
+                snapshot.translate(&graphene::Point::new(-width * 0.5, -height * 0.5));
 
-            self.snapshot_pattern(snapshot, &bounds);
+                self.snapshot_transition(snapshot, &bounds);
+
+                snapshot.restore();
+                snapshot.pop();
+            } else {
+                self.snapshot_transition(snapshot, &bounds);
+            }
+
+            if let Some(child) = widget.child() {
+                widget.snapshot_child(&child, snapshot);
+            }
         }
     }
 
     impl BinImpl for GradientBg {}
 
     impl GradientBg {
-        fn snapshot_gradient(&self, snapshot: &gtk::Snapshot, bounds: &graphene::Rect) {
-            let progress = self.progress.get();
-            let phase = self.phase.get() as usize;
+        fn pattern_texture(&self) -> Option<gdk::Texture> {
+            self.pattern
+                .borrow()
+                .as_ref()
+                .and_then(pattern::Pattern::texture)
+        }
+
+        fn set_pattern(&self, pattern: Option<gdk::Texture>) {
+            self.replace_pattern(pattern.map(pattern::Pattern::Texture));
+        }
+
+        pub(super) fn replace_pattern(&self, pattern: Option<pattern::Pattern>) {
+            self.begin_transition();
+            self.pattern.replace(pattern);
+            self.pattern_coverage.take();
+            self.obj().queue_draw();
+            self.obj().notify_pattern();
+        }
+
+        fn set_pattern_scale(&self, pattern_scale: f32) {
+            self.pattern_scale.set(pattern_scale.max(0.01));
+            self.obj().queue_draw();
+        }
+
+        fn set_photo(&self, photo: Option<gdk::Texture>) {
+            if *self.photo.borrow() == photo {
+                return;
+            }
+
+            let has_photo = photo.is_some();
+            self.begin_transition();
+            self.photo.replace(photo);
+            self.photo_sample.take();
+
+            // Photos aren't animated
+            if has_photo {
+                self.stop_animation();
+            } else if self.autoplay_allowed() && !self.is_animating() {
+                self.start_animation(self.direction());
+            }
+
+            self.obj().queue_draw();
+        }
+
+        fn set_blur(&self, blur: bool) {
+            if self.blur.get() != blur && self.photo.borrow().is_some() {
+                self.begin_transition();
+            }
+            self.blur.set(blur);
+            self.obj().queue_draw();
+        }
+
+        fn set_dim(&self, dim: f32) {
+            self.dim.set(dim.clamp(0.0, 1.0));
+            self.obj().queue_draw();
+        }
+
+        fn colors(&self) -> Vec<String> {
+            format_colors(&self.renderer.borrow())
+        }
+
+        /// Accepts any color format supported by [gdk::RGBA::parse]
+        fn set_colors(&self, colors: Vec<String>) {
+            match parse_colors(&colors) {
+                Ok(colors) => self.set_theme_colors(&colors),
+                Err(e) => log::warn!("can't parse gradient colors: {e}"),
+            }
+        }
+
+        pub(super) fn set_theme_colors(&self, colors: &[i32]) {
+            if colors.is_empty() {
+                log::warn!("gradient should have at least 1 color");
+                return;
+            }
+
+            self.update_gradient(|renderer| renderer.set_colors(colors));
+        }
+
+        pub(super) fn set_points(&self, points: Option<&[graphene::Point]>) {
+            self.update_gradient(|renderer| renderer.set_points(points));
+        }
+
+        pub(super) fn set_gradient(&self, colors: &[i32], points: Option<&[graphene::Point]>) {
+            if colors.is_empty() {
+                log::warn!("gradient should have at least 1 color");
+                return;
+            }
+
+            self.update_gradient(|renderer| renderer.set_gradient(colors, points));
+        }
+
+        /// Changes colors or custom points if they are valid together
+        ///
+        /// The change is checked on a copy of the renderer, so an invalid one keeps the old gradient.
+        fn update_gradient(
+            &self,
+            update: impl FnOnce(&mut GradientRenderer) -> Result<(), glib::BoolError>,
+        ) {
+            let mut gradient = self.renderer.borrow().clone();
+            if let Err(e) = update(&mut gradient) {
+                log::warn!("{e}");
+                return;
+            }
+
+            if gradient.same_gradient(&self.renderer.borrow()) {
+                return;
+            }
+
+            self.begin_transition();
+            self.renderer.borrow_mut().set_gradient_from(&gradient);
+            self.prepare_gradient_texture();
+
+            // Custom points aren't animated
+            if gradient.has_custom_points() {
+                self.stop_animation();
+            } else if self.autoplay_allowed() && !self.is_animating() {
+                self.start_animation(self.direction());
+            }
+
+            self.obj().queue_draw();
+            self.obj().notify_colors();
+        }
+
+        fn phase(&self) -> u32 {
+            self.renderer.borrow().phase()
+        }
+
+        fn set_phase(&self, phase: u32) {
+            self.stop_animation();
+
+            let mut renderer = self.renderer.borrow_mut();
+            renderer.set_phase(phase);
+            renderer.set_progress(0.0);
+            drop(renderer);
+
+            self.obj().queue_draw();
+            self.obj().notify_progress();
+        }
+
+        fn progress(&self) -> f32 {
+            self.renderer.borrow().progress()
+        }
+
+        fn set_progress(&self, progress: f32) {
+            self.stop_animation();
+
+            self.renderer.borrow_mut().set_progress(progress);
+            self.obj().queue_draw();
+        }
+
+        fn rotation(&self) -> i32 {
+            self.renderer.borrow().rotation()
+        }
+
+        fn set_rotation(&self, rotation: i32) {
+            if renderer::normalize_rotation(rotation) == self.rotation() {
+                return;
+            }
+
+            self.begin_transition();
+            self.renderer.borrow_mut().set_rotation(rotation);
+            self.prepare_gradient_texture();
+            self.obj().queue_draw();
+        }
+
+        fn color_space(&self) -> ColorSpace {
+            self.renderer.borrow().color_space()
+        }
+
+        fn set_color_space(&self, color_space: ColorSpace) {
+            if self.color_space() == color_space {
+                return;
+            }
+
+            self.begin_transition();
+            self.renderer.borrow_mut().set_color_space(color_space);
+            self.prepare_gradient_texture();
+            self.obj().queue_draw();
+        }
+
+        fn set_dark(&self, dark: bool) {
+            if self.dark.get() != dark {
+                self.begin_transition();
+            }
+            self.dark.set(dark);
+            self.obj().queue_draw();
+        }
+
+        fn set_intensity(&self, intensity: i32) {
+            let intensity = intensity.clamp(-100, 100);
+            if self.intensity.get() != intensity {
+                self.begin_transition();
+            }
+            self.intensity.set(intensity);
+            self.obj().queue_draw();
+        }
+
+        fn set_motion(&self, motion: bool) {
+            self.motion.set(motion);
+            self.obj().queue_draw();
+        }
+
+        fn duration(&self) -> u32 {
+            self.animation.get().unwrap().duration()
+        }
+
+        fn set_duration(&self, duration: u32) {
+            self.animation.get().unwrap().set_duration(duration);
+        }
+
+        fn transition_duration(&self) -> u32 {
+            self.transition.get().unwrap().duration()
+        }
+
+        fn set_transition_duration(&self, duration: u32) {
+            self.transition.get().unwrap().set_duration(duration);
+        }
+
+        fn easing(&self) -> adw::Easing {
+            self.animation.get().unwrap().easing()
+        }
+
+        fn set_easing(&self, easing: adw::Easing) {
+            self.animation.get().unwrap().set_easing(easing);
+        }
+
+        fn set_autoplay(&self, autoplay: bool) {
+            self.autoplay.set(autoplay);
+
+            if self.autoplay_allowed() && !self.is_animating() {
+                self.start_animation(self.direction());
+            }
+        }
+
+        /// Autoplay would finish animations instantly in a loop
+        /// when the widget isn't mapped or animations are disabled
+        fn autoplay_allowed(&self) -> bool {
+            let obj = self.obj();
+            self.autoplay.get()
+                && obj.is_mapped()
+                && obj.settings().is_gtk_enable_animations()
+                && self.is_animatable()
+        }
+
+        /// Only Telegram freeform gradients are animated
+        pub(super) fn is_animatable(&self) -> bool {
+            let renderer = self.renderer.borrow();
+            renderer.colors().len() >= 3
+                && !renderer.has_custom_points()
+                && self.photo.borrow().is_none()
+        }
+
+        pub(super) fn is_animating(&self) -> bool {
+            self.animation.get().unwrap().state() == adw::AnimationState::Playing
+        }
+
+        /// Step of autoplay and [animate](super::GradientBg::animate)
+        pub(super) fn direction(&self) -> i32 {
+            if self.reverse.get() {
+                -1
+            } else {
+                1
+            }
+        }
 
-            let cached_texture = (*self.gradient_texture.borrow()).clone();
+        /// Animates the given number of steps at once, negative steps go backwards
+        pub(super) fn start_animation(&self, steps: i32) {
+            let animation = self.animation.get().unwrap();
 
-            let texture = if let Some(texture) = cached_texture {
-                texture
+            let phase = self.renderer.borrow().phase() as i32;
+            let count = steps.unsigned_abs() as f64;
+
+            // The animation value is always counted from the earliest phase,
+            // so going backwards is the same animation played in reverse
+            if steps >= 0 {
+                self.animation_start_phase.set(phase as u32);
+                animation.set_value_from(0.0);
+                animation.set_value_to(count);
             } else {
-                let colors = &*self.colors.borrow();
+                let start_phase = (phase + steps).rem_euclid(renderer::PHASE_COUNT as i32);
+                self.animation_start_phase.set(start_phase as u32);
+                animation.set_value_from(count);
+                animation.set_value_to(0.0);
+            }
 
-                let positions = Self::calculate_positions(progress, phase);
+            animation.play();
 
-                // Even with 4x4 the upscaled result looks good,
-                // but I think that 32x32 is better
-                let (width, height) = (32, 32);
+            // Render every rest phase on the way ahead of time,
+            // so each step of the animation ends on a cached texture.
+            // Long animations pass the same phases again, there are only 8 of them
+            if self.shader.borrow().is_none() || !self.renderer.borrow().is_shader_compatible() {
+                let count = steps.unsigned_abs().min(renderer::PHASE_COUNT) as i32;
 
-                let raw_texture =
-                    software_gradient::generate_gradient(width, height, colors, &positions);
+                for step in 1..=count {
+                    let mut target = self.renderer.borrow().clone();
+                    let target_phase = phase + step * steps.signum();
+                    target.set_phase(target_phase.rem_euclid(renderer::PHASE_COUNT as i32) as u32);
+                    target.set_progress(0.0);
+
+                    if texture_cache::cached(&target, GRADIENT_TEXTURE_SIZE, GRADIENT_TEXTURE_SIZE)
+                        .is_none()
+                    {
+                        texture_cache::request(
+                            target,
+                            GRADIENT_TEXTURE_SIZE,
+                            GRADIENT_TEXTURE_SIZE,
+                            |_| {},
+                        );
+                    }
+                }
+            }
+        }
+
+        /// Stops the animation at the current frame and drops queued steps
+        fn stop_animation(&self) {
+            self.queued_steps.set(0);
+
+            if self.is_animating() {
+                // It goes back to the start of the animation,
+                // callers are expected to set the new state after that
+                self.animation.get().unwrap().reset();
+            }
+        }
+
+        /// Motion is disabled together with animations in the system settings
+        fn motion_enabled(&self) -> bool {
+            self.motion.get() && self.obj().settings().is_gtk_enable_animations()
+        }
 
-                let texture = gdk::MemoryTexture::new(
-                    width as i32,
-                    height as i32,
-                    gdk::MemoryFormat::B8g8r8a8,
-                    &glib::Bytes::from_owned(raw_texture),
-                    4 * width as usize,
+        pub(super) fn set_motion_target(&self, x: f32, y: f32) {
+            let target = (x.clamp(-1.0, 1.0), y.clamp(-1.0, 1.0));
+            self.motion_target.set(target);
+
+            if !self.motion_enabled() {
+                self.motion_offset.set(target);
+                return;
+            }
+
+            if self.motion_tick.borrow().is_some() {
+                return;
+            }
+
+            let tick = self.obj().add_tick_callback(|widget, _clock| {
+                let imp = widget.imp();
+
+                let (x, y) = imp.motion_offset.get();
+                let (target_x, target_y) = imp.motion_target.get();
+
+                let (x, y) = (
+                    x + (target_x - x) * MOTION_SMOOTHING,
+                    y + (target_y - y) * MOTION_SMOOTHING,
                 );
 
-                if progress == 0.0 {
-                    self.gradient_texture.replace(Some(texture.clone()));
+                widget.queue_draw();
+
+                if (target_x - x).abs() < 0.001 && (target_y - y).abs() < 0.001 {
+                    imp.motion_offset.set((target_x, target_y));
+                    imp.motion_tick.take();
+                    glib::ControlFlow::Break
+                } else {
+                    imp.motion_offset.set((x, y));
+                    glib::ControlFlow::Continue
                 }
+            });
+
+            self.motion_tick.replace(Some(tick));
+        }
 
-                texture
+        fn ensure_shader(&self) {
+            let widget = self.obj();
+            if self.shader.borrow().is_none() {
+                let renderer = widget.native().unwrap().renderer().unwrap();
+
+                let shader = gsk::GLShader::from_bytes(&GRADIENT_SHADER.into());
+                match shader.compile(&renderer) {
+                    Err(e) => {
+                        if !e.matches(gio::IOErrorEnum::NotSupported) {
+                            log::error!("can't compile the gradient shader {e}");
+                        }
+                    }
+                    Ok(_) => {
+                        self.shader.replace(Some(shader));
+                    }
+                }
             };
+        }
+
+        /// Draws the gradient, `exact_scale` is used to render it with the full resolution
+        /// instead of upscaling a small cached texture
+        fn snapshot_gradient(
+            &self,
+            snapshot: &gtk::Snapshot,
+            bounds: &graphene::Rect,
+            exact_scale: Option<f64>,
+        ) {
+            let renderer = self.renderer.borrow();
+
+            if renderer.is_shader_compatible() && exact_scale.is_none() {
+                drop(renderer);
+                if let Some(shader) = &*self.shader.borrow() {
+                    self.snapshot_shader_gradient(snapshot, bounds, shader);
+                    return;
+                }
+            }
+
+            let obj = self.obj().downgrade();
+
+            super::snapshot_gradient(
+                snapshot,
+                bounds,
+                &self.renderer.borrow(),
+                &self.gradient_texture,
+                exact_scale,
+                move || {
+                    if let Some(obj) = obj.upgrade() {
+                        obj.queue_draw();
+                    }
+                },
+            );
+        }
+
+        /// Renders the texture of the changed gradient right away
+        ///
+        /// Otherwise the stale texture would be shown until the background rendering is done
+        /// and a transition would fade the old gradient into itself.
+        fn prepare_gradient_texture(&self) {
+            let renderer = self.renderer.borrow().clone();
+
+            if renderer.is_shader_compatible() && self.shader.borrow().is_some() {
+                return;
+            }
 
-            snapshot.append_texture(&texture, &bounds);
+            self.gradient_texture
+                .prepare(&renderer, GRADIENT_TEXTURE_SIZE, GRADIENT_TEXTURE_SIZE);
         }
 
-        fn snapshot_pattern(&self, snapshot: &gtk::Snapshot, bounds: &graphene::Rect) {
-            let widget = self.obj();
-            let Some(pattern) = &*self.pattern.borrow() else {
-                // Nothing to snapshot
+        fn snapshot_shader_gradient(
+            &self,
+            snapshot: &gtk::Snapshot,
+            bounds: &graphene::Rect,
+            shader: &gsk::GLShader,
+        ) {
+            let renderer = self.renderer.borrow();
+
+            let colors = renderer.colors();
+            let positions = renderer.positions();
+
+            let args = gsk::ShaderArgsBuilder::new(shader, None);
+            args.set_int(0, colors.len().min(4) as i32);
+
+            for (i, (color, position)) in colors.iter().zip(&positions).take(4).enumerate() {
+                let i = i as i32;
+                args.set_vec3(1 + i, &color.to_vec3());
+                args.set_vec2(5 + i, &graphene::Vec2::new(position.x, position.y));
+            }
+
+            snapshot.push_gl_shader(shader, bounds, args.to_args());
+            snapshot.pop();
+        }
+
+        /// Composites the gradient and the pattern like Telegram clients do
+        ///
+        /// Positive intensity draws the pattern over the gradient with soft light blending,
+        /// negative intensity uses the pattern as a mask for the gradient over black background.
+        pub(super) fn snapshot_wallpaper(
+            &self,
+            snapshot: &gtk::Snapshot,
+            bounds: &graphene::Rect,
+            exact_scale: Option<f64>,
+        ) {
+            if let Some(photo) = &*self.photo.borrow() {
+                self.snapshot_photo(snapshot, bounds, photo);
+                return;
+            }
+
+            let scale = exact_scale.unwrap_or_else(|| self.obj().scale_factor() as f64);
+
+            let tile = self
+                .pattern
+                .borrow()
+                .as_ref()
+                .and_then(|pattern| pattern.tile(self.pattern_scale.get(), scale));
+
+            let Some((pattern, pattern_bounds)) = tile else {
+                self.snapshot_gradient(snapshot, bounds, exact_scale);
                 return;
             };
 
-            let pattern_bounds = graphene::Rect::new(
-                0.0,
-                0.0,
-                pattern.width() as f32 * 0.3,
-                pattern.height() as f32 * 0.3,
-            );
+            let intensity = self.intensity.get();
+            let opacity = intensity.unsigned_abs().min(100) as f64 / 100.0;
 
-            let mut matrix = [0.0; 16];
-            let mut offset = [0.0; 4];
-            if self.dark.get() {
-                matrix[15] = -0.3;
-                offset = [0.08; 4];
-                offset[3] = 1.0;
+            if intensity >= 0 {
+                snapshot.push_blend(gsk::BlendMode::SoftLight);
+                self.snapshot_gradient(snapshot, bounds, exact_scale);
+                snapshot.pop();
+
+                snapshot.push_opacity(opacity);
+                Self::snapshot_pattern(snapshot, bounds, &pattern, &pattern_bounds);
+                snapshot.pop();
+                snapshot.pop();
             } else {
-                matrix[15] = 0.1;
+                snapshot.append_color(&gdk::RGBA::BLACK, bounds);
+
+                snapshot.push_opacity(opacity);
+                snapshot.push_mask(gsk::MaskMode::Alpha);
+                Self::snapshot_pattern(snapshot, bounds, &pattern, &pattern_bounds);
+                snapshot.pop();
+                self.snapshot_gradient(snapshot, bounds, exact_scale);
+                snapshot.pop();
+                snapshot.pop();
             }
-            let color_matrix = graphene::Matrix::from_float(matrix);
-            let color_offset = graphene::Vec4::from_float(offset);
+        }
 
-            snapshot.push_color_matrix(&color_matrix, &color_offset);
-            snapshot.push_repeat(bounds, Some(&pattern_bounds));
-            snapshot.append_texture(pattern, &pattern_bounds);
-            snapshot.pop();
-            snapshot.pop();
+        /// Captures the current wallpaper to cross-fade it with the changed one
+        fn begin_transition(&self) {
+            let obj = self.obj();
+            let transition = self.transition.get().unwrap();
 
-            if let Some(child) = widget.child() {
-                widget.snapshot_child(&child, snapshot);
+            if transition.duration() == 0
+                || !obj.is_mapped()
+                || !obj.settings().is_gtk_enable_animations()
+                || obj.has_css_class("fallback")
+            {
+                return;
+            }
+
+            // Changes during the transition fade out the same frame,
+            // so quick changes like dragging a slider don't restart it every time
+            if transition.state() == adw::AnimationState::Playing
+                && self.transition_texture.borrow().is_some()
+            {
+                return;
+            }
+
+            let (width, height) = (obj.width() as f32, obj.height() as f32);
+            if width == 0.0 || height == 0.0 {
+                return;
+            }
+
+            let Some(renderer) = obj.native().and_then(|native| native.renderer()) else {
+                return;
+            };
+
+            let scale = obj.scale_factor() as f32;
+            let bounds = graphene::Rect::new(0.0, 0.0, width, height);
+
+            let snapshot = gtk::Snapshot::new();
+            snapshot.scale(scale, scale);
+            self.snapshot_wallpaper(&snapshot, &bounds, None);
+
+            let Some(node) = snapshot.to_node() else {
+                return;
+            };
+
+            // The frame is flattened, so it doesn't keep the whole wallpaper render tree
+            let viewport = graphene::Rect::new(0.0, 0.0, width * scale, height * scale);
+            let texture = renderer.render_texture(node, Some(&viewport));
+
+            self.transition_texture.replace(Some(texture));
+
+            transition.reset();
+            transition.play();
+        }
+
+        fn snapshot_transition(&self, snapshot: &gtk::Snapshot, bounds: &graphene::Rect) {
+            match &*self.transition_texture.borrow() {
+                Some(texture) => {
+                    snapshot.push_cross_fade(self.transition.get().unwrap().value());
+                    snapshot.append_texture(texture, bounds);
+                    snapshot.pop();
+                    self.snapshot_wallpaper(snapshot, bounds, None);
+                    snapshot.pop();
+                }
+                None => self.snapshot_wallpaper(snapshot, bounds, None),
             }
         }
 
-        fn calculate_positions(progress: f32, phase: usize) -> Vec<software_gradient::Point> {
-            let mut current = software_gradient::gather_positions(phase);
-            let next = software_gradient::gather_positions(phase + 1);
+        /// Scales the photo to cover the bounds, blurs and dims it
+        fn snapshot_photo(
+            &self,
+            snapshot: &gtk::Snapshot,
+            bounds: &graphene::Rect,
+            photo: &gdk::Texture,
+        ) {
+            let photo_width = photo.width() as f32;
+            let photo_height = photo.height() as f32;
 
-            if progress > 0.0 {
-                current
-                    .iter_mut()
-                    .zip(next)
-                    .for_each(|(current, next)| *current = current.interpolate(next, progress))
+            if photo_width == 0.0 || photo_height == 0.0 {
+                return;
             }
 
-            current
+            let scale = (bounds.width() / photo_width).max(bounds.height() / photo_height);
+            let width = photo_width * scale;
+            let height = photo_height * scale;
+
+            let mut photo_bounds = graphene::Rect::new(
+                bounds.x() + (bounds.width() - width) * 0.5,
+                bounds.y() + (bounds.height() - height) * 0.5,
+                width,
+                height,
+            );
+
+            snapshot.push_clip(bounds);
+
+            if self.blur.get() {
+                // Blur makes edges transparent, so they are moved out of the bounds
+                photo_bounds = photo_bounds.inset_r(-PHOTO_BLUR_RADIUS, -PHOTO_BLUR_RADIUS);
+
+                snapshot.push_blur(PHOTO_BLUR_RADIUS as f64);
+                snapshot.append_texture(photo, &photo_bounds);
+                snapshot.pop();
+            } else {
+                snapshot.append_texture(photo, &photo_bounds);
+            }
+
+            let dim = self.dim.get();
+            if self.dark.get() && dim > 0.0 {
+                snapshot.append_color(&gdk::RGBA::new(0.0, 0.0, 0.0, dim), bounds);
+            }
+
+            snapshot.pop();
+        }
+
+        /// Draws repeated pattern tinted to black
+        fn snapshot_pattern(
+            snapshot: &gtk::Snapshot,
+            bounds: &graphene::Rect,
+            pattern: &gdk::Texture,
+            pattern_bounds: &graphene::Rect,
+        ) {
+            // Keep only the alpha channel
+            let mut matrix = [0.0; 16];
+            matrix[15] = 1.0;
+            let color_matrix = graphene::Matrix::from_float(matrix);
+
+            snapshot.push_color_matrix(&color_matrix, &graphene::Vec4::zero());
+            snapshot.push_repeat(bounds, Some(pattern_bounds));
+            snapshot.append_texture(pattern, pattern_bounds);
+            snapshot.pop();
+            snapshot.pop();
         }
     }
 }
 
 glib::wrapper! {
+    #[doc(alias = "OriGradientBg")]
+    /// Telegram-like wallpaper with animated gradient and pattern
+    ///
+    /// # Properties
+    ///
+    /// * colors: [Vec]<[String]>.
+    /// From 1 to 4 colors in any format supported by [gdk::RGBA::parse].
+    /// One color makes a solid wallpaper, two colors make a linear gradient
+    /// and three or four colors make a freeform gradient.
+    /// With [custom points](GradientBg::set_points) there should be as many colors as points.
+    ///
+    /// * dark: [bool].
+    /// Dims photo wallpapers, the sign of the intensity is kept as the server sends it,
+    /// dark wallpapers usually come with negative intensity.
+    ///
+    /// * pattern: [Option]<[gdk::Texture]>.
+    /// Single color pattern drawn over the gradient,
+    /// see [set_pattern_bytes](GradientBg::set_pattern_bytes) for svg patterns.
+    ///
+    /// * pattern-scale: [f32].
+    /// Size of a pattern tile relative to the size of the pattern image.
+    ///
+    /// * photo: [Option]<[gdk::Texture]>.
+    /// Image wallpaper scaled to cover the widget, it's drawn instead of the gradient and the pattern.
+    /// Photos aren't animated, but the motion mode works with them.
+    ///
+    /// * blur: [bool].
+    /// Blurs the photo like the "blur" mode of Telegram wallpapers.
+    ///
+    /// * dim: [f32] between 0 and 1.
+    /// Opacity of the black layer over the photo in dark mode, 0.3 by default.
+    ///
+    /// * phase: [u32] between 0 and 7.
+    /// Current phase of the freeform gradient animation.
+    /// Setting it stops the animation.
+    ///
+    /// * progress: [f32] between 0 and 1.
+    /// Transition from the current phase to the next one.
+    /// Setting it stops the animation, it can be animated with [adw::PropertyAnimationTarget].
+    ///
+    /// * intensity: [i32] between -100 and 100.
+    /// Pattern intensity as in Telegram wallpaper settings.
+    /// Positive values darken the gradient with the pattern,
+    /// negative values show the gradient only through the pattern over black background.
+    ///
+    /// * rotation: [i32].
+    /// Rotation of two color gradients in degrees, multiple of 45.
+    /// 0 means that the first color is at the top.
+    ///
+    /// * color-space: [ColorSpace].
+    /// Color space in which colors are mixed, [Srgb](ColorSpace::Srgb) like in Telegram by default.
+    ///
+    /// * transition-duration: [u32].
+    /// Duration of the cross-fade in milliseconds when colors, dark mode, intensity,
+    /// rotation, pattern or photo change, 300 by default. 0 disables the cross-fade.
+    ///
+    /// * duration: [u32].
+    /// Duration of one animation in milliseconds, 200 by default.
+    ///
+    /// * easing: [adw::Easing].
+    /// Easing of the animation, [EaseInOutQuad](adw::Easing::EaseInOutQuad) by default.
+    ///
+    /// * autoplay: [bool].
+    /// Animates the gradient continuously while the widget is visible,
+    /// useful for login and empty screens.
+    ///
+    /// * reverse: [bool].
+    /// Makes [animate](GradientBg::animate) and autoplay go to the previous phase.
+    ///
+    /// * motion: [bool].
+    /// Enables Telegram-like "motion" mode, the wallpaper is zoomed a bit and smoothly follows
+    /// the pointer or the [motion adjustment](GradientBg::set_motion_adjustment).
+    /// It does nothing when animations are disabled.
+    ///
+    /// # Bluerpint example
+    /// ```blp
+    /// $OriGradientBg {
+    ///     colors: ["#94dae9", "#9aeddb", "#94c3f6", "#ac96f7"];
+    ///     intensity: -50;
+    ///     motion: true;
+    /// }
+    /// ```
     pub struct GradientBg(ObjectSubclass<imp::GradientBg>)
         @extends gtk::Widget, adw::Bin;
 }
@@ -219,36 +1161,311 @@ impl GradientBg {
         glib::Object::new()
     }
 
-    pub fn set_pattern(&self, pattern: Option<gdk::Texture>) {
-        self.imp().pattern.replace(pattern);
+    /// Sets pattern from svg or Telegram `.tgv` data
+    ///
+    /// Unlike [set_pattern](Self::set_pattern), the pattern is rasterized again
+    /// when the scale factor or the pattern scale changes, so it stays sharp.
+    pub fn set_pattern_bytes(&self, bytes: &glib::Bytes) -> Result<(), glib::Error> {
+        let pattern = pattern::VectorPattern::from_bytes(bytes)?;
+        self.imp()
+            .replace_pattern(Some(pattern::Pattern::Vector(pattern)));
+        Ok(())
     }
 
-    pub fn set_dark(&self, dark: bool) {
-        self.imp().dark.set(dark);
+    /// Loads pattern from svg or Telegram `.tgv` file
+    ///
+    /// See [set_pattern_bytes](Self::set_pattern_bytes)
+    pub fn set_pattern_file(&self, file: &impl IsA<gio::File>) -> Result<(), glib::Error> {
+        let (bytes, _) = file.load_bytes(gio::Cancellable::NONE)?;
+        self.set_pattern_bytes(&bytes)
     }
 
-    // Takes int colors as from theme returned by tdlib
+    /// Takes int colors as from theme returned by tdlib
+    ///
+    /// One color makes a solid wallpaper, two colors make a linear gradient
+    /// and three or four colors make a freeform gradient.
+    /// Empty lists and lists with more than 4 colors are ignored,
+    /// with [custom points](Self::set_points) there should be as many colors as points.
     pub fn set_theme_colors(&self, colors: &[i32]) {
+        self.imp().set_theme_colors(colors);
+    }
+
+    /// Same as [set_theme_colors](Self::set_theme_colors), alpha is ignored
+    pub fn set_rgba_colors(&self, colors: &[gdk::RGBA]) {
+        let colors: Vec<_> = colors.iter().map(rgba_to_int).collect();
+        self.imp().set_theme_colors(&colors);
+    }
+
+    /// Places colors at custom points for non-Telegram gradients
+    ///
+    /// See [GradientRenderer::set_points], the points are ignored if they don't match the colors.
+    /// Use [set_gradient](Self::set_gradient) to change the number of colors and points at once.
+    /// Gradients with custom points aren't animated.
+    pub fn set_points(&self, points: Option<&[graphene::Point]>) {
+        self.imp().set_points(points);
+    }
+
+    /// Replaces int colors and custom points together
+    ///
+    /// See [GradientRenderer::set_gradient], nothing is changed if they don't match.
+    pub fn set_gradient(&self, colors: &[i32], points: Option<&[graphene::Point]>) {
+        self.imp().set_gradient(colors, points);
+    }
+
+    /// Returns a copy of the renderer with the current state of the gradient
+    pub(crate) fn renderer(&self) -> GradientRenderer {
+        self.imp().renderer.borrow().clone()
+    }
+
+    /// Renders the wallpaper without the child the same way it's displayed
+    ///
+    /// `width` and `height` are in logical pixels and `scale` is the scale factor,
+    /// so the size of the texture is multiplied by `scale`.
+    /// The gradient is rendered with the full resolution and the motion mode is ignored.
+    ///
+    /// Fails if the widget isn't in a window and there is no display for a temporary renderer.
+    pub fn render_to_texture(
+        &self,
+        width: i32,
+        height: i32,
+        scale: f64,
+    ) -> Result<gdk::Texture, glib::BoolError> {
+        let bounds = graphene::Rect::new(0.0, 0.0, width.max(1) as f32, height.max(1) as f32);
+
+        let snapshot = gtk::Snapshot::new();
+        snapshot.scale(scale as f32, scale as f32);
+        snapshot.push_clip(&bounds);
+        self.imp()
+            .snapshot_wallpaper(&snapshot, &bounds, Some(scale));
+        snapshot.pop();
+
+        let viewport = graphene::Rect::new(
+            0.0,
+            0.0,
+            bounds.width() * scale as f32,
+            bounds.height() * scale as f32,
+        );
+
+        let node = snapshot
+            .to_node()
+            .unwrap_or_else(|| gsk::ColorNode::new(&gdk::RGBA::TRANSPARENT, &viewport).upcast());
+
+        // Use the renderer of the window if possible,
+        // otherwise the wallpaper can be rendered without showing the widget
+        let (renderer, temporary) = match self.native().and_then(|native| native.renderer()) {
+            Some(renderer) => (renderer, false),
+            None => {
+                let display = gdk::Display::default()
+                    .ok_or_else(|| glib::bool_error!("no display to render the wallpaper"))?;
+
+                let renderer = gsk::CairoRenderer::new();
+                renderer
+                    .realize_for_display(&display)
+                    .map_err(|e| glib::bool_error!("can't realize renderer: {e}"))?;
+
+                (renderer.upcast(), true)
+            }
+        };
+
+        let texture = renderer.render_texture(node, Some(&viewport));
+
+        if temporary {
+            renderer.unrealize();
+        }
+
+        Ok(texture)
+    }
+
+    /// Saves the wallpaper to a png file, see [render_to_texture](Self::render_to_texture)
+    pub fn save_to_png(
+        &self,
+        path: impl AsRef<std::path::Path>,
+        width: i32,
+        height: i32,
+        scale: f64,
+    ) -> Result<(), glib::BoolError> {
+        self.render_to_texture(width, height, scale)?
+            .save_to_png(path)
+    }
+
+    /// Returns the average color of the wallpaper with the pattern or the photo
+    ///
+    /// The area is in widget coordinates, the whole widget is used without an area.
+    /// The color is computed from a small gradient, the average alpha of the pattern
+    /// and a downscaled copy of the photo, which are cached,
+    /// so it's cheap enough to call on scroll.
+    pub fn average_color(&self, area: Option<&graphene::Rect>) -> gdk::RGBA {
         let imp = self.imp();
+        let (width, height) = (self.width() as f32, self.height() as f32);
+
+        let bounds = graphene::Rect::new(0.0, 0.0, width, height);
+        let area = match area {
+            Some(area) if width > 0.0 && height > 0.0 => *area,
+            _ => bounds,
+        };
+
+        if let Some(photo) = &*imp.photo.borrow() {
+            let mut sample = imp.photo_sample.borrow_mut();
+            let sample = sample.get_or_insert_with(|| {
+                service_colors::TextureSample::new(photo, PHOTO_SAMPLE_SIZE)
+            });
+
+            // Same placement as in snapshot_photo
+            let (photo_width, photo_height) = (photo.width() as f32, photo.height() as f32);
+            let scale = (width / photo_width).max(height / photo_height);
+            let (photo_width, photo_height) = (photo_width * scale, photo_height * scale);
+            let offset_x = (width - photo_width) * 0.5;
+            let offset_y = (height - photo_height) * 0.5;
+
+            let mut color = sample.average_color(&graphene::Rect::new(
+                (area.x() - offset_x) / photo_width,
+                (area.y() - offset_y) / photo_height,
+                area.width() / photo_width,
+                area.height() / photo_height,
+            ));
+
+            let dim = imp.dim.get();
+            if imp.dark.get() && dim > 0.0 {
+                color = gdk::RGBA::new(
+                    color.red() * (1.0 - dim),
+                    color.green() * (1.0 - dim),
+                    color.blue() * (1.0 - dim),
+                    color.alpha(),
+                );
+            }
+
+            return color;
+        }
 
-        let colors = colors
-            .iter()
-            .map(|&int| software_gradient::Color::from_int_rgb(int))
-            .collect();
+        let relative_area = if width > 0.0 && height > 0.0 {
+            graphene::Rect::new(
+                area.x() / width,
+                area.y() / height,
+                area.width() / width,
+                area.height() / height,
+            )
+        } else {
+            graphene::Rect::new(0.0, 0.0, 1.0, 1.0)
+        };
 
-        imp.colors.replace(colors);
+        let gradient = imp.renderer.borrow().average_color_in(&relative_area);
 
-        imp.gradient_texture.take();
-        self.queue_draw();
+        let coverage = match (imp.pattern_coverage.get(), &*imp.pattern.borrow()) {
+            (_, None) => return gradient,
+            (Some(coverage), Some(_)) => coverage,
+            (None, Some(pattern)) => {
+                let coverage = pattern.coverage();
+                imp.pattern_coverage.set(Some(coverage));
+                coverage
+            }
+        };
+
+        let intensity = imp.intensity.get();
+        let opacity = intensity.unsigned_abs().min(100) as f32 / 100.0;
+        let coverage = coverage * opacity;
+
+        // Same compositing as in snapshot_wallpaper, applied to the averages
+        let composite = |channel: f32| {
+            if intensity >= 0 {
+                // Soft light with black turns a channel into its square
+                channel + coverage * (channel * channel - channel)
+            } else {
+                // The gradient is visible only through the pattern over black
+                channel * coverage
+            }
+        };
+
+        gdk::RGBA::new(
+            composite(gradient.red()),
+            composite(gradient.green()),
+            composite(gradient.blue()),
+            1.0,
+        )
+    }
+
+    /// Suggests colors for service messages and buttons over the area of the wallpaper
+    ///
+    /// See [average_color](Self::average_color)
+    pub fn service_colors(&self, area: Option<&graphene::Rect>) -> ServiceColors {
+        ServiceColors::from_average(&self.average_color(area))
+    }
+
+    /// Moves the wallpaper according to the adjustment instead of the pointer
+    ///
+    /// Useful with the vertical adjustment of a scrolled window.
+    pub fn set_motion_adjustment(&self, adjustment: Option<&gtk::Adjustment>) {
+        let imp = self.imp();
+
+        if let Some((adjustment, handler)) = imp.motion_adjustment.take() {
+            adjustment.disconnect(handler);
+        }
+
+        let Some(adjustment) = adjustment else {
+            imp.set_motion_target(0.0, 0.0);
+            return;
+        };
+
+        let update = clone!(
+            #[weak(rename_to = obj)]
+            self,
+            move |adjustment: &gtk::Adjustment| {
+                let range = adjustment.upper() - adjustment.page_size() - adjustment.lower();
+                let y = if range > 0.0 {
+                    (adjustment.value() - adjustment.lower()) / range * 2.0 - 1.0
+                } else {
+                    0.0
+                };
+                obj.imp().set_motion_target(0.0, y as f32);
+            }
+        );
+
+        update(adjustment);
+        let handler = adjustment.connect_value_changed(update);
+
+        imp.motion_adjustment
+            .replace(Some((adjustment.clone(), handler)));
     }
 
+    /// Moves the freeform gradient to the next phase,
+    /// or to the previous one if [reverse](Self::reverse) is set
+    ///
+    /// Solid and linear wallpapers aren't animated.
+    /// If the animation is already playing, the step is queued.
     pub fn animate(&self) {
-        let animation = self.imp().animation.get().unwrap();
+        self.animate_by(self.imp().direction());
+    }
+
+    /// Moves the freeform gradient by `steps` phases in a single animation
+    ///
+    /// Negative steps play the animation backwards, for example to undo sending a message.
+    /// Like in Telegram, this is useful when several messages are sent at once.
+    /// If the animation is already playing, the steps are queued
+    /// and played together after the current animation.
+    pub fn animate_by(&self, steps: i32) {
+        let imp = self.imp();
 
-        let val = animation.value();
-        if val == 0.0 || val == 1.0 {
-            animation.play()
+        if steps == 0 || !imp.is_animatable() {
+            return;
         }
+
+        if imp.is_animating() {
+            imp.queued_steps.set(imp.queued_steps.get() + steps);
+        } else {
+            imp.start_animation(steps);
+        }
+    }
+
+    /// Jumps to a fractional position of the animation without animating
+    ///
+    /// The integer part is the phase and the fractional part is the progress
+    /// to the next phase, so it can be used for scrubbing previews.
+    /// Negative positions and positions after the last phase are wrapped.
+    pub fn seek(&self, position: f64) {
+        let position = position.rem_euclid(renderer::PHASE_COUNT as f64);
+        let phase = position.floor();
+
+        self.set_phase(phase as u32);
+        self.set_progress((position - phase) as f32);
     }
 }
 
@@ -257,3 +1474,101 @@ impl Default for GradientBg {
         Self::new()
     }
 }
+
+/// Converts color to int like in themes returned by tdlib, alpha is ignored
+pub(crate) fn rgba_to_int(color: &gdk::RGBA) -> i32 {
+    let [r, g, b] = [color.red(), color.green(), color.blue()]
+        .map(|channel| (channel.clamp(0.0, 1.0) * 255.0).round() as i32);
+    (r << 16) | (g << 8) | b
+}
+
+/// Parses colors in any format supported by [gdk::RGBA::parse]
+fn parse_colors(colors: &[String]) -> Result<Vec<i32>, glib::BoolError> {
+    colors
+        .iter()
+        .map(|color| gdk::RGBA::parse(color.as_str()).map(|color| rgba_to_int(&color)))
+        .collect()
+}
+
+/// Returns colors of the renderer as hex strings
+fn format_colors(renderer: &GradientRenderer) -> Vec<String> {
+    renderer
+        .colors()
+        .iter()
+        .map(|color| format!("#{:06x}", color.to_int_rgb()))
+        .collect()
+}
+
+/// Draws the gradient without the shader
+///
+/// Textures of freeform gradients are cached and upscaled,
+/// new states are rendered in background by `texture` and `redraw` is called when they are ready.
+/// `exact_scale` is used to render them with the full resolution instead.
+pub(crate) fn snapshot_gradient(
+    snapshot: &gtk::Snapshot,
+    bounds: &graphene::Rect,
+    renderer: &GradientRenderer,
+    texture: &AnimatedTexture,
+    exact_scale: Option<f64>,
+    redraw: impl FnOnce() + 'static,
+) {
+    if snapshot_simple_gradient(snapshot, bounds, renderer) {
+        return;
+    }
+
+    let texture = if let Some(scale) = exact_scale {
+        let width = (bounds.width() as f64 * scale).ceil() as u32;
+        let height = (bounds.height() as f64 * scale).ceil() as u32;
+
+        renderer.render_texture(width.max(1), height.max(1))
+    } else {
+        texture.texture(
+            renderer,
+            GRADIENT_TEXTURE_SIZE,
+            GRADIENT_TEXTURE_SIZE,
+            redraw,
+        )
+    };
+
+    snapshot.append_texture(&texture, bounds);
+}
+
+/// Draws solid and linear gradients with GSK nodes
+///
+/// Returns `false` if the gradient needs a texture.
+fn snapshot_simple_gradient(
+    snapshot: &gtk::Snapshot,
+    bounds: &graphene::Rect,
+    renderer: &GradientRenderer,
+) -> bool {
+    let custom_points = renderer.has_custom_points();
+
+    match *renderer.colors() {
+        [] => {}
+        [color] if !custom_points => snapshot.append_color(&color.to_rgba(), bounds),
+        // GSK mixes colors only in sRGB
+        [first, second] if !custom_points && renderer.color_space() == ColorSpace::Srgb => {
+            let (start, end) = software_gradient::linear_gradient_line(renderer.rotation());
+
+            let to_bounds = |point: software_gradient::Point| {
+                graphene::Point::new(
+                    bounds.x() + point.x * bounds.width(),
+                    bounds.y() + point.y * bounds.height(),
+                )
+            };
+
+            snapshot.append_linear_gradient(
+                bounds,
+                &to_bounds(start),
+                &to_bounds(end),
+                &[
+                    gsk::ColorStop::new(0.0, first.to_rgba()),
+                    gsk::ColorStop::new(1.0, second.to_rgba()),
+                ],
+            );
+        }
+        _ => return false,
+    }
+
+    true
+}
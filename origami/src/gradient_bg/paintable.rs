@@ -0,0 +1,398 @@
+use std::cell::Cell;
+use std::cell::RefCell;
+use std::time::Duration;
+
+use adw::prelude::*;
+use gtk::gdk;
+use gtk::glib;
+use gtk::graphene;
+use gtk::subclass::prelude::*;
+
+use super::renderer;
+use super::snapshot_gradient;
+use super::texture_cache;
+use super::ColorSpace;
+use super::FollowedGradient;
+use super::GradientBg;
+use super::GradientRenderer;
+use super::DEFAULT_DURATION;
+use super::GRADIENT_TEXTURE_SIZE;
+
+/// Interval between frames of the animation, the paintable has no frame clock
+const FRAME_INTERVAL: Duration = Duration::from_millis(16);
+
+/// Animation of the paintable driven by a timer
+pub(super) struct Animation {
+    source: glib::SourceId,
+    /// Monotonic time of the start in microseconds
+    start_time: i64,
+    start_phase: u32,
+    steps: i32,
+}
+
+mod imp {
+    use super::*;
+
+    #[derive(Default, glib::Properties)]
+    #[properties(wrapper_type = super::GradientPaintable)]
+    pub struct GradientPaintable {
+        #[property(name = "colors", get = Self::colors, set = Self::set_colors, type = Vec<String>)]
+        #[property(
+            name = "phase",
+            get = Self::phase,
+            set = Self::set_phase,
+            maximum = renderer::PHASE_COUNT - 1,
+            type = u32
+        )]
+        #[property(
+            name = "progress",
+            get = Self::progress,
+            set = Self::set_progress,
+            minimum = 0.0,
+            maximum = 1.0,
+            type = f32
+        )]
+        #[property(name = "rotation", get = Self::rotation, set = Self::set_rotation, type = i32)]
+        #[property(
+            name = "color-space",
+            get = Self::color_space,
+            set = Self::set_color_space,
+            type = ColorSpace,
+            builder(ColorSpace::Srgb)
+        )]
+        pub(super) gradient: FollowedGradient,
+        #[property(get, set, default = DEFAULT_DURATION)]
+        pub(super) duration: Cell<u32>,
+        pub(super) animation: RefCell<Option<Animation>>,
+        pub(super) queued_steps: Cell<i32>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for GradientPaintable {
+        const NAME: &'static str = "OriGradientPaintable";
+        type Type = super::GradientPaintable;
+        type Interfaces = (gdk::Paintable,);
+    }
+
+    impl ObjectImpl for GradientPaintable {
+        fn properties() -> &'static [glib::ParamSpec] {
+            Self::derived_properties()
+        }
+
+        fn property(&self, id: usize, pspec: &glib::ParamSpec) -> glib::Value {
+            self.derived_property(id, pspec)
+        }
+
+        fn set_property(&self, id: usize, value: &glib::Value, pspec: &glib::ParamSpec) {
+            self.derived_set_property(id, value, pspec)
+        }
+
+        fn constructed(&self) {
+            self.parent_constructed();
+            self.duration.set(DEFAULT_DURATION);
+        }
+
+        fn dispose(&self) {
+            self.stop_animation();
+            self.gradient.unfollow();
+        }
+    }
+
+    impl PaintableImpl for GradientPaintable {
+        fn current_image(&self) -> gdk::Paintable {
+            texture_cache::texture(
+                &self.gradient.renderer.borrow(),
+                GRADIENT_TEXTURE_SIZE,
+                GRADIENT_TEXTURE_SIZE,
+            )
+            .upcast()
+        }
+
+        fn flags(&self) -> gdk::PaintableFlags {
+            // The gradient is stretched to any size
+            gdk::PaintableFlags::SIZE
+        }
+
+        fn snapshot(&self, snapshot: &gdk::Snapshot, width: f64, height: f64) {
+            let Some(snapshot) = snapshot.downcast_ref::<gtk::Snapshot>() else {
+                return;
+            };
+
+            let bounds = graphene::Rect::new(0.0, 0.0, width as f32, height as f32);
+            let obj = self.obj().downgrade();
+
+            snapshot_gradient(
+                snapshot,
+                &bounds,
+                &self.gradient.renderer.borrow(),
+                &self.gradient.texture,
+                None,
+                move || {
+                    if let Some(obj) = obj.upgrade() {
+                        obj.invalidate_contents();
+                    }
+                },
+            );
+        }
+    }
+
+    impl GradientPaintable {
+        /// Only freeform gradients with Telegram points are animated
+        fn is_animatable(&self) -> bool {
+            let renderer = self.gradient.renderer.borrow();
+            renderer.colors().len() >= 3 && !renderer.has_custom_points()
+        }
+
+        pub(super) fn animate_by(&self, steps: i32) {
+            if steps == 0 || !self.is_animatable() {
+                return;
+            }
+
+            if self.animation.borrow().is_some() {
+                self.queued_steps.set(self.queued_steps.get() + steps);
+            } else {
+                self.start_animation(steps);
+            }
+        }
+
+        /// Animates the given number of steps at once like [GradientBg] does
+        fn start_animation(&self, steps: i32) {
+            let phase = self.gradient.phase() as i32;
+
+            // The position is always counted from the earliest phase,
+            // so going backwards is the same animation played in reverse
+            let start_phase = if steps >= 0 {
+                phase
+            } else {
+                (phase + steps).rem_euclid(renderer::PHASE_COUNT as i32)
+            };
+
+            let obj = self.obj().downgrade();
+            let source = glib::timeout_add_local(FRAME_INTERVAL, move || {
+                let Some(obj) = obj.upgrade() else {
+                    return glib::ControlFlow::Break;
+                };
+
+                obj.imp().animation_tick()
+            });
+
+            self.animation.replace(Some(Animation {
+                source,
+                start_time: glib::monotonic_time(),
+                start_phase: start_phase as u32,
+                steps,
+            }));
+        }
+
+        fn animation_tick(&self) -> glib::ControlFlow {
+            let Some((start_time, start_phase, steps)) =
+                self.animation.borrow().as_ref().map(|animation| {
+                    (animation.start_time, animation.start_phase, animation.steps)
+                })
+            else {
+                return glib::ControlFlow::Break;
+            };
+
+            let elapsed = (glib::monotonic_time() - start_time) as f64 / 1000.0;
+            let duration = self.duration.get().max(1) as f64;
+            let t = (elapsed / duration).min(1.0);
+
+            let count = steps.unsigned_abs() as f64;
+            let eased = adw::Easing::EaseInOutQuad.ease(t) * count;
+            let value = if steps >= 0 { eased } else { count - eased };
+
+            let passed_steps = value.floor();
+            let phase = start_phase + passed_steps as u32;
+
+            let mut renderer = self.gradient.renderer.borrow_mut();
+            let phase_changed = renderer.phase() != phase % renderer::PHASE_COUNT;
+            renderer.set_phase(phase);
+            renderer.set_progress((value - passed_steps) as f32);
+            drop(renderer);
+
+            let obj = self.obj();
+            if phase_changed {
+                obj.notify_phase();
+            }
+            obj.notify_progress();
+            obj.invalidate_contents();
+
+            if t < 1.0 {
+                return glib::ControlFlow::Continue;
+            }
+
+            // The source is removed by returning Break
+            self.animation.take();
+
+            let queued_steps = self.queued_steps.replace(0);
+            if queued_steps != 0 {
+                self.start_animation(queued_steps);
+            }
+
+            glib::ControlFlow::Break
+        }
+
+        /// Stops the animation at the current frame and drops queued steps
+        pub(super) fn stop_animation(&self) {
+            self.queued_steps.set(0);
+
+            if let Some(animation) = self.animation.take() {
+                animation.source.remove();
+            }
+        }
+
+        fn colors(&self) -> Vec<String> {
+            self.gradient.colors()
+        }
+
+        fn set_colors(&self, colors: Vec<String>) {
+            if self.gradient.set_colors(&colors) {
+                self.colors_changed();
+            }
+        }
+
+        pub(super) fn set_theme_colors(&self, colors: &[i32]) {
+            if self.gradient.set_theme_colors(colors) {
+                self.colors_changed();
+            }
+        }
+
+        pub(super) fn set_gradient_from(&self, source: &GradientRenderer) {
+            if !self.gradient.set_gradient_from(source) {
+                return;
+            }
+
+            if source.has_custom_points() {
+                self.stop_animation();
+            }
+
+            self.colors_changed();
+        }
+
+        fn colors_changed(&self) {
+            self.obj().invalidate_contents();
+            self.obj().notify_colors();
+        }
+
+        fn phase(&self) -> u32 {
+            self.gradient.phase()
+        }
+
+        fn set_phase(&self, phase: u32) {
+            self.stop_animation();
+            self.gradient.set_phase(phase);
+            self.obj().invalidate_contents();
+        }
+
+        fn progress(&self) -> f32 {
+            self.gradient.progress()
+        }
+
+        fn set_progress(&self, progress: f32) {
+            self.stop_animation();
+            self.gradient.set_progress(progress);
+            self.obj().invalidate_contents();
+        }
+
+        fn rotation(&self) -> i32 {
+            self.gradient.rotation()
+        }
+
+        fn set_rotation(&self, rotation: i32) {
+            self.gradient.set_rotation(rotation);
+            self.obj().invalidate_contents();
+        }
+
+        fn color_space(&self) -> ColorSpace {
+            self.gradient.color_space()
+        }
+
+        fn set_color_space(&self, color_space: ColorSpace) {
+            self.gradient.set_color_space(color_space);
+            self.obj().invalidate_contents();
+        }
+    }
+}
+
+glib::wrapper! {
+    #[doc(alias = "OriGradientPaintable")]
+    /// Gradient of [GradientBg] as a [gdk::Paintable]
+    ///
+    /// Can be used in a [gtk::Picture], as a thumbnail in a theme list
+    /// or drawn in a custom snapshot. It has no intrinsic size and fills the whole area.
+    /// Contents are invalidated on every change of the properties,
+    /// so animating [phase](Self::phase) and [progress](Self::progress)
+    /// with [adw::PropertyAnimationTarget] or binding them to a [GradientBg] animates it.
+    /// [animate](Self::animate) plays the animation with a timer, so it works without widgets.
+    ///
+    /// # Properties
+    ///
+    /// * colors, phase, progress, rotation, color-space.
+    /// Same as in [GradientBg], [follow_gradient_bg](Self::follow_gradient_bg) binds them to a wallpaper.
+    /// Setting phase or progress stops the animation.
+    ///
+    /// * duration: [u32].
+    /// Duration of [animate](Self::animate) in milliseconds, 200 by default.
+    pub struct GradientPaintable(ObjectSubclass<imp::GradientPaintable>)
+        @implements gdk::Paintable;
+}
+
+impl GradientPaintable {
+    pub fn new() -> Self {
+        glib::Object::new()
+    }
+
+    /// Creates a paintable for int colors as from theme returned by tdlib
+    pub fn with_theme_colors(colors: &[i32]) -> Self {
+        let paintable = Self::new();
+        paintable.set_theme_colors(colors);
+        paintable
+    }
+
+    /// Takes int colors as from theme returned by tdlib, same as [GradientBg::set_theme_colors]
+    pub fn set_theme_colors(&self, colors: &[i32]) {
+        self.imp().set_theme_colors(colors);
+    }
+
+    /// Moves the freeform gradient to the next phase with animation
+    ///
+    /// The animation is driven by a timer, so the paintable doesn't need a widget.
+    /// Gradients with less than 3 colors or custom points aren't animated.
+    pub fn animate(&self) {
+        self.animate_by(1);
+    }
+
+    /// Moves the freeform gradient by `steps` phases in a single animation
+    ///
+    /// Negative steps play the animation backwards.
+    /// If the animation is already playing, the steps are queued
+    /// and played together after the current animation.
+    pub fn animate_by(&self, steps: i32) {
+        self.imp().animate_by(steps);
+    }
+
+    /// Binds colors and animation of the paintable to the [GradientBg]
+    ///
+    /// Custom points are copied together with the colors.
+    /// Bindings to the previously followed widget are removed.
+    pub fn follow_gradient_bg(&self, gradient_bg: &GradientBg) {
+        let imp = self.imp();
+
+        imp.stop_animation();
+        imp.gradient.follow(
+            self,
+            gradient_bg,
+            glib::clone!(
+                #[weak(rename_to = paintable)]
+                self,
+                move |renderer| paintable.imp().set_gradient_from(renderer)
+            ),
+        );
+    }
+}
+
+impl Default for GradientPaintable {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,200 @@
+use std::cell::Cell;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use gtk::gdk;
+use gtk::gdk_pixbuf;
+use gtk::gio;
+use gtk::glib;
+use gtk::graphene;
+use gtk::prelude::*;
+
+use super::service_colors::TextureSample;
+
+/// Size of the sample used to compute the pattern coverage
+const COVERAGE_SAMPLE_SIZE: u32 = 64;
+
+/// Magic bytes of gzip stream, `.tgv` files are gzip-compressed svg
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+
+pub(super) enum Pattern {
+    /// Already decoded pattern, it's only scaled
+    Texture(gdk::Texture),
+    /// Pattern that is rasterized again for every new size
+    Vector(VectorPattern),
+}
+
+impl Pattern {
+    /// Returns the texture or the last rasterized image of the vector pattern
+    pub(super) fn texture(&self) -> Option<gdk::Texture> {
+        match self {
+            Self::Texture(texture) => Some(texture.clone()),
+            Self::Vector(pattern) => pattern.texture.borrow().clone(),
+        }
+    }
+
+    /// Returns the average alpha of the pattern
+    ///
+    /// Vector patterns are rasterized separately at a small size,
+    /// so the texture for the widget stays cached.
+    pub(super) fn coverage(&self) -> f32 {
+        match self {
+            Self::Texture(texture) => TextureSample::new(texture, COVERAGE_SAMPLE_SIZE).coverage(),
+            Self::Vector(pattern) => {
+                let (width, height) = pattern.size;
+                let scale = COVERAGE_SAMPLE_SIZE as f32 / width.max(height).max(1) as f32;
+                let size = (
+                    ((width as f32 * scale).ceil() as i32).max(1),
+                    ((height as f32 * scale).ceil() as i32).max(1),
+                );
+
+                match rasterize(&pattern.bytes, Some(size)) {
+                    Ok((pixbuf, _)) => {
+                        #[allow(deprecated)]
+                        let texture = gdk::Texture::for_pixbuf(&pixbuf);
+                        TextureSample::new(&texture, COVERAGE_SAMPLE_SIZE).coverage()
+                    }
+                    Err(e) => {
+                        log::error!("can't rasterize the pattern {e}");
+                        0.0
+                    }
+                }
+            }
+        }
+    }
+
+    /// Returns the texture and bounds of one tile
+    ///
+    /// `pattern_scale` is the size of the tile relative to the size of the pattern image
+    /// and `scale` is the scale factor of the output.
+    pub(super) fn tile(
+        &self,
+        pattern_scale: f32,
+        scale: f64,
+    ) -> Option<(gdk::Texture, graphene::Rect)> {
+        match self {
+            Self::Texture(texture) => {
+                let bounds = graphene::Rect::new(
+                    0.0,
+                    0.0,
+                    texture.width() as f32 * pattern_scale,
+                    texture.height() as f32 * pattern_scale,
+                );
+                Some((texture.clone(), bounds))
+            }
+            Self::Vector(pattern) => {
+                let (width, height) = pattern.size;
+                let bounds = graphene::Rect::new(
+                    0.0,
+                    0.0,
+                    width as f32 * pattern_scale,
+                    height as f32 * pattern_scale,
+                );
+
+                let pixel_width = (bounds.width() as f64 * scale).ceil() as i32;
+                let pixel_height = (bounds.height() as f64 * scale).ceil() as i32;
+
+                match pattern.texture(pixel_width.max(1), pixel_height.max(1)) {
+                    Ok(texture) => Some((texture, bounds)),
+                    Err(e) => {
+                        log::error!("can't rasterize the pattern {e}");
+                        None
+                    }
+                }
+            }
+        }
+    }
+}
+
+pub(super) struct VectorPattern {
+    bytes: glib::Bytes,
+    /// Size of the image as declared in the file
+    size: (i32, i32),
+    texture: RefCell<Option<gdk::Texture>>,
+}
+
+impl VectorPattern {
+    /// Accepts svg or `.tgv` bytes
+    pub(super) fn from_bytes(bytes: &glib::Bytes) -> Result<Self, glib::Error> {
+        let bytes = if bytes.starts_with(GZIP_MAGIC) {
+            decompress(bytes)?
+        } else {
+            bytes.clone()
+        };
+
+        // Load the smallest possible image, we need only the intrinsic size
+        let (_, size) = rasterize(&bytes, Some((1, 1)))?;
+
+        Ok(Self {
+            bytes,
+            size,
+            texture: Default::default(),
+        })
+    }
+
+    fn texture(&self, width: i32, height: i32) -> Result<gdk::Texture, glib::Error> {
+        if let Some(texture) = &*self.texture.borrow() {
+            if texture.width() == width && texture.height() == height {
+                return Ok(texture.clone());
+            }
+        }
+
+        let (pixbuf, _) = rasterize(&self.bytes, Some((width, height)))?;
+
+        #[allow(deprecated)]
+        let texture = gdk::Texture::for_pixbuf(&pixbuf);
+
+        self.texture.replace(Some(texture.clone()));
+
+        Ok(texture)
+    }
+}
+
+fn decompress(bytes: &glib::Bytes) -> Result<glib::Bytes, glib::Error> {
+    let decompressor = gio::ZlibDecompressor::new(gio::ZlibCompressorFormat::Gzip);
+    let input = gio::MemoryInputStream::from_bytes(bytes);
+    let stream = gio::ConverterInputStream::new(&input, &decompressor);
+
+    let mut data = Vec::with_capacity(bytes.len() * 4);
+    loop {
+        let chunk = stream.read_bytes(64 * 1024, gio::Cancellable::NONE)?;
+        if chunk.is_empty() {
+            break;
+        }
+        data.extend_from_slice(&chunk);
+    }
+
+    Ok(glib::Bytes::from_owned(data))
+}
+
+/// Returns the image with the requested size and the intrinsic size of the image
+fn rasterize(
+    bytes: &glib::Bytes,
+    size: Option<(i32, i32)>,
+) -> Result<(gdk_pixbuf::Pixbuf, (i32, i32)), glib::Error> {
+    let loader = gdk_pixbuf::PixbufLoader::new();
+
+    let intrinsic_size = Rc::new(Cell::new((0, 0)));
+
+    loader.connect_size_prepared({
+        let intrinsic_size = intrinsic_size.clone();
+        move |loader, width, height| {
+            intrinsic_size.set((width, height));
+            if let Some((width, height)) = size {
+                loader.set_size(width, height);
+            }
+        }
+    });
+
+    loader.write_bytes(bytes)?;
+    loader.close()?;
+
+    let pixbuf = loader.pixbuf().ok_or_else(|| {
+        glib::Error::new(
+            gdk_pixbuf::PixbufError::CorruptImage,
+            "no image in the pattern",
+        )
+    })?;
+
+    Ok((pixbuf, intrinsic_size.get()))
+}
@@ -0,0 +1,95 @@
+use super::GradientBg;
+
+/// Built-in Telegram wallpaper with light and dark variants
+///
+/// # Example
+/// ```no_run
+/// # let gradient_bg = origami::GradientBg::new();
+/// let preset = origami::GradientPreset::by_name("classic").unwrap();
+/// preset.apply(&gradient_bg);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GradientPreset {
+    /// Lowercase name of the preset
+    pub name: &'static str,
+    /// Int colors like in themes returned by tdlib
+    pub colors: &'static [i32],
+    pub dark_colors: &'static [i32],
+    /// Pattern intensity from -100 to 100
+    pub intensity: i32,
+    pub dark_intensity: i32,
+}
+
+const PRESETS: &[GradientPreset] = &[
+    GradientPreset {
+        name: "classic",
+        colors: &[0xdbddbb, 0x6ba587, 0xd5d88d, 0x88b884],
+        dark_colors: &[0xfec496, 0xdd6cb9, 0x962fbf, 0x4f5bd5],
+        intensity: 50,
+        dark_intensity: -50,
+    },
+    GradientPreset {
+        name: "day",
+        colors: &[0x94dae9, 0x9aeddb, 0x94c3f6, 0xac96f7],
+        dark_colors: &[0xd6932e, 0xbc40db, 0x4280d7, 0x614ed5],
+        intensity: 50,
+        dark_intensity: -50,
+    },
+    GradientPreset {
+        name: "night",
+        colors: &[0x8a9bc8, 0x6c7fa6, 0x9c97c9, 0x757bb0],
+        dark_colors: &[0x6c7fa6, 0x2e344b, 0x7874a7, 0x333258],
+        intensity: 40,
+        dark_intensity: -40,
+    },
+    GradientPreset {
+        name: "arctic",
+        colors: &[0xb1e1ed, 0xdaedf4, 0x89c4da, 0xc4d8f0],
+        dark_colors: &[0x4d8fb6, 0x29587a, 0x6ab2d4, 0x3a6e99],
+        intensity: 45,
+        dark_intensity: -55,
+    },
+];
+
+impl GradientPreset {
+    /// Returns every built-in preset, the first one is the default Telegram wallpaper
+    pub fn all() -> &'static [GradientPreset] {
+        PRESETS
+    }
+
+    /// Finds a preset by its name, ignoring the case
+    pub fn by_name(name: &str) -> Option<&'static GradientPreset> {
+        PRESETS
+            .iter()
+            .find(|preset| preset.name.eq_ignore_ascii_case(name))
+    }
+
+    /// Returns colors of the light or dark variant
+    pub fn colors(&self, dark: bool) -> &'static [i32] {
+        if dark {
+            self.dark_colors
+        } else {
+            self.colors
+        }
+    }
+
+    /// Returns pattern intensity of the light or dark variant
+    pub fn intensity(&self, dark: bool) -> i32 {
+        if dark {
+            self.dark_intensity
+        } else {
+            self.intensity
+        }
+    }
+
+    /// Applies the variant matching [dark](GradientBg::dark) of the widget
+    ///
+    /// The pattern is kept, so it should be applied again when `dark` changes.
+    pub fn apply(&self, gradient_bg: &GradientBg) {
+        let dark = gradient_bg.dark();
+
+        gradient_bg.set_theme_colors(self.colors(dark));
+        gradient_bg.set_intensity(self.intensity(dark));
+        gradient_bg.set_rotation(0);
+    }
+}
@@ -0,0 +1,474 @@
+use gtk::gdk;
+use gtk::glib;
+use gtk::graphene;
+
+use super::color_space::ColorSpace;
+use super::service_colors;
+use super::software_gradient;
+use super::software_gradient::Color;
+use super::software_gradient::PixelFormat;
+use super::software_gradient::Point;
+use super::MAX_COLORS;
+
+/// Number of phases in one full turn of the gradient animation
+pub(crate) const PHASE_COUNT: u32 = 8;
+
+/// Maximum number of custom control points
+pub(super) const MAX_POINTS: usize = 8;
+
+/// Size of the image used to compute average colors, the gradient is smooth enough
+const AVERAGE_SIZE: u32 = 32;
+
+/// Telegram-like gradient renderer that doesn't need a widget
+///
+/// It produces the same image as [GradientBg](super::GradientBg) at any size,
+/// so it can be used for thumbnails, previews or from a non-GUI thread.
+/// Like the widget, the gradient is stretched over non-square images.
+///
+/// Like in Telegram the type of the wallpaper depends on the number of colors:
+/// one color is a solid fill, two colors make a linear gradient rotated by
+/// [rotation](Self::rotation) and three or four colors make an animated freeform gradient.
+///
+/// Non-Telegram gradients can use [custom control points](Self::set_points)
+/// and mix colors in a [perceptual color space](Self::set_color_space).
+///
+/// # Example
+/// ```no_run
+/// let mut renderer = origami::GradientRenderer::new(&[0x94dae9, 0x9aeddb, 0x94c3f6, 0xac96f7]);
+/// renderer.set_phase(3);
+///
+/// let texture = renderer.render_texture(128, 128);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GradientRenderer {
+    colors: Vec<Color>,
+    phase: u32,
+    progress: f32,
+    rotation: i32,
+    color_space: ColorSpace,
+    points: Option<Vec<Point>>,
+}
+
+impl GradientRenderer {
+    /// Creates a renderer for int colors as from theme returned by tdlib
+    ///
+    /// Lists with more than 4 colors are ignored, see [set_gradient](Self::set_gradient).
+    pub fn new(colors: &[i32]) -> Self {
+        let mut renderer = Self::default();
+        if let Err(e) = renderer.set_colors(colors) {
+            log::warn!("{e}");
+        }
+        renderer
+    }
+
+    /// Replaces colors with int colors as from theme returned by tdlib
+    ///
+    /// Fails if the number of colors doesn't match the [custom points](Self::set_points)
+    /// or if there are more than 4 colors without them.
+    pub fn set_colors(&mut self, colors: &[i32]) -> Result<(), glib::BoolError> {
+        validate_gradient(colors.len(), self.points.as_ref().map(Vec::len))?;
+        self.colors = colors.iter().map(|&int| Color::from_int_rgb(int)).collect();
+        Ok(())
+    }
+
+    pub(super) fn colors(&self) -> &[Color] {
+        &self.colors
+    }
+
+    pub fn phase(&self) -> u32 {
+        self.phase
+    }
+
+    /// Sets the animation phase, it's wrapped to the number of phases
+    pub fn set_phase(&mut self, phase: u32) {
+        self.phase = phase % PHASE_COUNT;
+    }
+
+    pub fn progress(&self) -> f32 {
+        self.progress
+    }
+
+    /// Sets the transition progress from the current phase to the next one
+    ///
+    /// The value is clamped between 0 and 1
+    pub fn set_progress(&mut self, progress: f32) {
+        self.progress = progress.clamp(0.0, 1.0);
+    }
+
+    pub fn rotation(&self) -> i32 {
+        self.rotation
+    }
+
+    /// Sets rotation of two color gradients in degrees
+    ///
+    /// The value is rounded to a multiple of 45 like in Telegram wallpaper settings
+    pub fn set_rotation(&mut self, rotation: i32) {
+        self.rotation = normalize_rotation(rotation);
+    }
+
+    pub fn color_space(&self) -> ColorSpace {
+        self.color_space
+    }
+
+    /// Sets the color space in which colors are mixed, sRGB by default
+    pub fn set_color_space(&mut self, color_space: ColorSpace) {
+        self.color_space = color_space;
+    }
+
+    /// Returns custom control points if they are set
+    pub fn points(&self) -> Option<Vec<graphene::Point>> {
+        self.points.as_ref().map(|points| {
+            points
+                .iter()
+                .map(|point| graphene::Point::new(point.x, point.y))
+                .collect()
+        })
+    }
+
+    /// Places colors at custom points instead of the animated Telegram positions
+    ///
+    /// Coordinates are relative to the size of the gradient, from 0 to 1.
+    /// Every color is paired with a point in order, so there should be as many points as colors,
+    /// from 1 to 8, otherwise the points aren't changed and an error is returned.
+    /// The gradient is always freeform with custom points and it isn't animated,
+    /// phase and progress are ignored. `None` returns to the Telegram positions.
+    ///
+    /// Use [set_gradient](Self::set_gradient) to change the number of colors and points at once.
+    pub fn set_points(
+        &mut self,
+        points: Option<&[graphene::Point]>,
+    ) -> Result<(), glib::BoolError> {
+        validate_gradient(self.colors.len(), points.map(<[_]>::len))?;
+        self.points = points.map(convert_points);
+        Ok(())
+    }
+
+    /// Replaces colors and custom points together
+    ///
+    /// See [set_colors](Self::set_colors) and [set_points](Self::set_points),
+    /// nothing is changed if they don't match.
+    pub fn set_gradient(
+        &mut self,
+        colors: &[i32],
+        points: Option<&[graphene::Point]>,
+    ) -> Result<(), glib::BoolError> {
+        validate_gradient(colors.len(), points.map(<[_]>::len))?;
+        self.colors = colors.iter().map(|&int| Color::from_int_rgb(int)).collect();
+        self.points = points.map(convert_points);
+        Ok(())
+    }
+
+    /// Copies colors and custom points of another renderer
+    pub(crate) fn set_gradient_from(&mut self, other: &GradientRenderer) {
+        self.colors = other.colors.clone();
+        self.points = other.points.clone();
+    }
+
+    /// Returns `true` if both renderers have the same colors and custom points
+    pub(crate) fn same_gradient(&self, other: &GradientRenderer) -> bool {
+        self.colors == other.colors && self.points == other.points
+    }
+
+    pub(super) fn has_custom_points(&self) -> bool {
+        self.points.is_some()
+    }
+
+    /// Returns `true` if the gradient can be drawn by the GPU shader
+    ///
+    /// The shader supports only Telegram gradients with up to 4 colors mixed in sRGB.
+    pub(super) fn is_shader_compatible(&self) -> bool {
+        self.points.is_none()
+            && self.color_space == ColorSpace::Srgb
+            && (3..=4).contains(&self.colors.len())
+    }
+
+    /// Returns buffer for a texture with BGRA8 format.
+    ///
+    /// Rows are tightly packed, so the stride is `4 * width`.
+    /// Gradients are dithered, so there are no visible bands even in dark colors.
+    pub fn render(&self, width: u32, height: u32) -> Box<[u8]> {
+        self.render_pixels(width, height, PixelFormat::Bgra8)
+    }
+
+    /// Renders the gradient in the given format, rows are tightly packed
+    pub(super) fn render_pixels(&self, width: u32, height: u32, format: PixelFormat) -> Box<[u8]> {
+        match (&*self.colors, &self.points) {
+            ([], _) => vec![0; format.bytes_per_pixel() * width as usize * height as usize]
+                .into_boxed_slice(),
+            (colors, Some(points)) => software_gradient::generate_gradient(
+                width,
+                height,
+                colors,
+                points,
+                self.color_space,
+                format,
+            ),
+            (&[color], None) => software_gradient::generate_solid(width, height, color, format),
+            (&[first, second], None) => software_gradient::generate_linear_gradient(
+                width,
+                height,
+                [first, second],
+                self.rotation,
+                self.color_space,
+                format,
+            ),
+            (colors, None) => {
+                let positions = self.positions();
+                software_gradient::generate_gradient(
+                    width,
+                    height,
+                    colors,
+                    &positions,
+                    self.color_space,
+                    format,
+                )
+            }
+        }
+    }
+
+    /// Same as [render](Self::render) but swaps channels to RGBA8 format
+    pub fn render_rgba(&self, width: u32, height: u32) -> Box<[u8]> {
+        let mut buffer = self.render(width, height);
+        buffer
+            .chunks_exact_mut(4)
+            .for_each(|pixel| pixel.swap(0, 2));
+        buffer
+    }
+
+    /// Renders the gradient into a new texture
+    pub fn render_texture(&self, width: u32, height: u32) -> gdk::MemoryTexture {
+        let format = PixelFormat::Bgra8;
+        Self::texture_from_buffer(
+            width,
+            height,
+            self.render_pixels(width, height, format),
+            format,
+        )
+    }
+
+    /// Wraps the result of [render_pixels](Self::render_pixels),
+    /// so it can be rendered on another thread
+    pub(super) fn texture_from_buffer(
+        width: u32,
+        height: u32,
+        buffer: Box<[u8]>,
+        format: PixelFormat,
+    ) -> gdk::MemoryTexture {
+        let memory_format = match format {
+            PixelFormat::Bgra8 => gdk::MemoryFormat::B8g8r8a8,
+            PixelFormat::Rgba16Float => gdk::MemoryFormat::R16g16b16a16Float,
+        };
+
+        gdk::MemoryTexture::new(
+            width as i32,
+            height as i32,
+            memory_format,
+            &glib::Bytes::from_owned(buffer),
+            format.bytes_per_pixel() * width as usize,
+        )
+    }
+
+    /// Returns the average color of the whole gradient
+    pub fn average_color(&self) -> gdk::RGBA {
+        self.average_color_in(&graphene::Rect::new(0.0, 0.0, 1.0, 1.0))
+    }
+
+    /// Returns the average color of the area
+    ///
+    /// Coordinates of the area are relative to the size of the gradient, from 0 to 1.
+    pub fn average_color_in(&self, area: &graphene::Rect) -> gdk::RGBA {
+        let buffer = self.render(AVERAGE_SIZE, AVERAGE_SIZE);
+        let size = AVERAGE_SIZE as f32;
+
+        service_colors::average_color(
+            &buffer,
+            AVERAGE_SIZE,
+            AVERAGE_SIZE,
+            4 * AVERAGE_SIZE as usize,
+            &graphene::Rect::new(
+                area.x() * size,
+                area.y() * size,
+                area.width() * size,
+                area.height() * size,
+            ),
+        )
+    }
+
+    pub(super) fn positions(&self) -> Vec<Point> {
+        if let Some(points) = &self.points {
+            return points.clone();
+        }
+
+        let phase = self.phase as usize;
+
+        let mut current = software_gradient::gather_positions(phase);
+        let next = software_gradient::gather_positions(phase + 1);
+
+        if self.progress > 0.0 {
+            current
+                .iter_mut()
+                .zip(next)
+                .for_each(|(current, next)| *current = current.interpolate(next, self.progress))
+        }
+
+        current
+    }
+}
+
+/// Checks that the colors can be drawn with the custom points
+///
+/// Telegram gradients have up to 4 colors,
+/// with custom points every color needs its own point and there can be up to 8 of them.
+pub(super) fn validate_gradient(
+    color_count: usize,
+    point_count: Option<usize>,
+) -> Result<(), glib::BoolError> {
+    match point_count {
+        None if color_count > MAX_COLORS => Err(glib::bool_error!(
+            "gradient should have up to {MAX_COLORS} colors without custom points, got {color_count}"
+        )),
+        Some(point_count) if !(1..=MAX_POINTS).contains(&point_count) => Err(glib::bool_error!(
+            "gradient should have 1 to {MAX_POINTS} custom points, got {point_count}"
+        )),
+        Some(point_count) if point_count != color_count => Err(glib::bool_error!(
+            "gradient has {color_count} colors for {point_count} custom points"
+        )),
+        _ => Ok(()),
+    }
+}
+
+fn convert_points(points: &[graphene::Point]) -> Vec<Point> {
+    points
+        .iter()
+        .map(|point| Point {
+            x: point.x().clamp(0.0, 1.0),
+            y: point.y().clamp(0.0, 1.0),
+        })
+        .collect()
+}
+
+/// Rounds the rotation to a multiple of 45 degrees between 0 and 360
+pub(super) fn normalize_rotation(rotation: i32) -> i32 {
+    ((rotation as f32 / 45.0).round() as i32 * 45).rem_euclid(360)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::GRADIENT_TEXTURE_SIZE;
+    use super::*;
+
+    fn points(count: usize) -> Vec<graphene::Point> {
+        (0..count)
+            .map(|i| graphene::Point::new(i as f32 / count as f32, 0.5))
+            .collect()
+    }
+
+    #[test]
+    fn points_must_match_colors() {
+        let colors = [0xff0000, 0x00ff00, 0x0000ff];
+        let mut renderer = GradientRenderer::new(&colors);
+
+        assert!(renderer.set_points(Some(&[])).is_err());
+        assert!(renderer.set_points(Some(&points(2))).is_err());
+        assert!(renderer.set_points(Some(&points(4))).is_err());
+        assert!(!renderer.has_custom_points());
+
+        assert!(renderer.set_points(Some(&points(3))).is_ok());
+        assert!(renderer.has_custom_points());
+        assert!(renderer.set_colors(&[0xffffff; 4]).is_err());
+        assert_eq!(renderer.colors().len(), 3);
+    }
+
+    #[test]
+    fn many_colors_need_points() {
+        let mut renderer = GradientRenderer::new(&[0xffffff; 8]);
+        assert!(renderer.colors().is_empty());
+
+        assert!(renderer.set_colors(&[0xffffff; 5]).is_err());
+        assert!(renderer
+            .set_gradient(&[0xffffff; 8], Some(&points(8)))
+            .is_ok());
+        assert!(renderer
+            .set_gradient(&[0xffffff; 9], Some(&points(9)))
+            .is_err());
+
+        // 8 colors can't stay without their points
+        assert!(renderer.set_points(None).is_err());
+        assert!(renderer.has_custom_points());
+
+        assert!(renderer.set_gradient(&[0xffffff; 4], None).is_ok());
+        assert!(!renderer.has_custom_points());
+    }
+
+    #[test]
+    fn validate_counts() {
+        assert!(validate_gradient(0, None).is_ok());
+        assert!(validate_gradient(MAX_COLORS, None).is_ok());
+        assert!(validate_gradient(MAX_COLORS + 1, None).is_err());
+        assert!(validate_gradient(0, Some(0)).is_err());
+        assert!(validate_gradient(1, Some(1)).is_ok());
+        assert!(validate_gradient(MAX_POINTS, Some(MAX_POINTS)).is_ok());
+        assert!(validate_gradient(MAX_POINTS + 1, Some(MAX_POINTS + 1)).is_err());
+        assert!(validate_gradient(2, Some(3)).is_err());
+    }
+
+    /// Averages blocks of BGRA8 pixels, so the image gets the target size
+    fn downscale(
+        buffer: &[u8],
+        (width, height): (u32, u32),
+        (target_width, target_height): (u32, u32),
+    ) -> Vec<[f32; 3]> {
+        let (block_width, block_height) = (width / target_width, height / target_height);
+        let mut pixels = vec![[0.0; 3]; (target_width * target_height) as usize];
+
+        for y in 0..height {
+            for x in 0..width {
+                let offset = 4 * (y * width + x) as usize;
+                let target = ((y / block_height) * target_width + x / block_width) as usize;
+
+                for (channel, byte) in pixels[target].iter_mut().zip(&buffer[offset..offset + 3]) {
+                    *channel += *byte as f32 / (block_width * block_height) as f32;
+                }
+            }
+        }
+
+        pixels
+    }
+
+    #[test]
+    fn exact_render_matches_stretched_texture() {
+        let renderer = GradientRenderer::new(&[0xdbddbb, 0x6ba587, 0xd5d88d, 0x88b884]);
+
+        // The widget stretches a square texture over any size
+        let size = GRADIENT_TEXTURE_SIZE;
+        let texture = downscale(&renderer.render(size, size), (size, size), (size, size));
+
+        for (width, height) in [(640, 320), (320, 640)] {
+            let export = downscale(
+                &renderer.render(width, height),
+                (width, height),
+                (size, size),
+            );
+
+            for (index, (expected, actual)) in texture.iter().zip(&export).enumerate() {
+                for (expected, actual) in expected.iter().zip(actual) {
+                    assert!(
+                        (expected - actual).abs() <= 4.0,
+                        "{width}x{height}, pixel {index}: expected {expected}, got {actual}"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn rotation_is_normalized() {
+        assert_eq!(normalize_rotation(0), 0);
+        assert_eq!(normalize_rotation(45), 45);
+        assert_eq!(normalize_rotation(-45), 315);
+        assert_eq!(normalize_rotation(360), 0);
+        assert_eq!(normalize_rotation(400), 45);
+        assert_eq!(normalize_rotation(30), 45);
+        assert_eq!(normalize_rotation(-90), 270);
+    }
+}
@@ -0,0 +1,220 @@
+use gtk::gdk;
+use gtk::graphene;
+use gtk::prelude::*;
+
+use super::color_space::srgb_to_linear;
+
+/// Opacity of the service bubble over the wallpaper
+const SERVICE_ALPHA: f32 = 0.4;
+
+/// Minimum contrast between the foreground and the service bubble over the wallpaper
+const MIN_CONTRAST: f32 = 4.5;
+
+/// Colors for service messages, date separators and buttons over a wallpaper
+///
+/// Like in Telegram, the translucent background is derived from the average color
+/// of the wallpaper under it, see [GradientBg::service_colors](super::GradientBg::service_colors).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ServiceColors {
+    /// Translucent background of the bubble
+    pub background: gdk::RGBA,
+    /// White or black color for the text, whichever has more contrast
+    pub foreground: gdk::RGBA,
+}
+
+impl ServiceColors {
+    /// Derives colors from the average color of the wallpaper
+    ///
+    /// The background is a more saturated and darker version of the color.
+    pub fn from_average(average: &gdk::RGBA) -> Self {
+        let (hue, saturation, value) =
+            gtk::rgb_to_hsv(average.red(), average.green(), average.blue());
+
+        let saturation = (saturation + 0.05 + 0.1 * (1.0 - saturation)).min(1.0);
+        let value = value * 0.65;
+
+        let (red, green, blue) = gtk::hsv_to_rgb(hue, saturation, value);
+        let background = gdk::RGBA::new(red, green, blue, SERVICE_ALPHA);
+
+        // The text is read against the bubble blended with the wallpaper
+        let blended = gdk::RGBA::new(
+            mix(average.red(), red, SERVICE_ALPHA),
+            mix(average.green(), green, SERVICE_ALPHA),
+            mix(average.blue(), blue, SERVICE_ALPHA),
+            1.0,
+        );
+
+        let white_contrast = contrast_ratio(&gdk::RGBA::WHITE, &blended);
+        let foreground = if white_contrast >= MIN_CONTRAST
+            || white_contrast >= contrast_ratio(&gdk::RGBA::BLACK, &blended)
+        {
+            gdk::RGBA::WHITE
+        } else {
+            gdk::RGBA::BLACK
+        };
+
+        Self {
+            background,
+            foreground,
+        }
+    }
+}
+
+/// Contrast ratio of two opaque colors as defined by WCAG, from 1 to 21
+pub fn contrast_ratio(first: &gdk::RGBA, second: &gdk::RGBA) -> f32 {
+    let first = relative_luminance(first);
+    let second = relative_luminance(second);
+
+    (first.max(second) + 0.05) / (first.min(second) + 0.05)
+}
+
+fn relative_luminance(color: &gdk::RGBA) -> f32 {
+    0.2126 * srgb_to_linear(color.red())
+        + 0.7152 * srgb_to_linear(color.green())
+        + 0.0722 * srgb_to_linear(color.blue())
+}
+
+fn mix(from: f32, to: f32, factor: f32) -> f32 {
+    from * (1.0 - factor) + to * factor
+}
+
+/// Averages BGRA8 pixels inside of the area in pixels
+///
+/// Pixels can be premultiplied, the alpha is averaged separately.
+/// At least one pixel is always taken, so small areas return the local color.
+pub(super) fn average_color(
+    pixels: &[u8],
+    width: u32,
+    height: u32,
+    stride: usize,
+    area: &graphene::Rect,
+) -> gdk::RGBA {
+    if width == 0 || height == 0 {
+        return gdk::RGBA::TRANSPARENT;
+    }
+
+    let clamp_x = |x: f32| (x.max(0.0) as u32).min(width - 1);
+    let clamp_y = |y: f32| (y.max(0.0) as u32).min(height - 1);
+
+    let x0 = clamp_x(area.x().floor());
+    let y0 = clamp_y(area.y().floor());
+    let x1 = clamp_x((area.x() + area.width()).ceil() - 1.0).max(x0);
+    let y1 = clamp_y((area.y() + area.height()).ceil() - 1.0).max(y0);
+
+    let mut sum = [0u64; 4];
+    for y in y0..=y1 {
+        let row = &pixels[y as usize * stride..];
+        for x in x0..=x1 {
+            let pixel = &row[4 * x as usize..4 * x as usize + 4];
+            sum.iter_mut()
+                .zip(pixel)
+                .for_each(|(sum, &channel)| *sum += channel as u64);
+        }
+    }
+
+    let [blue, green, red, alpha] = sum;
+    if alpha == 0 {
+        return gdk::RGBA::TRANSPARENT;
+    }
+
+    let count = ((x1 - x0 + 1) * (y1 - y0 + 1)) as f32;
+    let unpremultiply = |channel: u64| (channel as f32 / alpha as f32).min(1.0);
+
+    gdk::RGBA::new(
+        unpremultiply(red),
+        unpremultiply(green),
+        unpremultiply(blue),
+        alpha as f32 / count / 255.0,
+    )
+}
+
+/// Downscaled copy of a texture for computing average colors
+///
+/// The texture is downloaded once, so averages of different areas are cheap.
+pub(super) struct TextureSample {
+    /// Premultiplied BGRA8 pixels
+    pixels: Box<[u8]>,
+    width: u32,
+    height: u32,
+}
+
+impl TextureSample {
+    /// Averages blocks of the texture, so the sample is at most `max_size` pixels on each side
+    pub(super) fn new(texture: &impl IsA<gdk::Texture>, max_size: u32) -> Self {
+        let texture = texture.as_ref();
+        let (texture_width, texture_height) = (texture.width() as u32, texture.height() as u32);
+
+        if texture_width == 0 || texture_height == 0 {
+            return Self {
+                pixels: Box::new([]),
+                width: 0,
+                height: 0,
+            };
+        }
+
+        let mut downloader = gdk::TextureDownloader::new(texture);
+        downloader.set_format(gdk::MemoryFormat::B8g8r8a8Premultiplied);
+        let (bytes, stride) = downloader.download_bytes();
+
+        let width = texture_width.min(max_size);
+        let height = texture_height.min(max_size);
+
+        let mut pixels = Vec::with_capacity(4 * width as usize * height as usize);
+
+        for y in 0..height {
+            for x in 0..width {
+                let area = graphene::Rect::new(
+                    (x * texture_width / width) as f32,
+                    (y * texture_height / height) as f32,
+                    (texture_width / width) as f32,
+                    (texture_height / height) as f32,
+                );
+
+                let color = average_color(&bytes, texture_width, texture_height, stride, &area);
+                let alpha = color.alpha();
+
+                pixels.extend(
+                    [
+                        color.blue() * alpha,
+                        color.green() * alpha,
+                        color.red() * alpha,
+                        alpha,
+                    ]
+                    .map(|channel| (channel * 255.0).round() as u8),
+                );
+            }
+        }
+
+        Self {
+            pixels: pixels.into_boxed_slice(),
+            width,
+            height,
+        }
+    }
+
+    /// Returns the average color of the area
+    ///
+    /// Coordinates of the area are relative to the size of the texture, from 0 to 1.
+    pub(super) fn average_color(&self, area: &graphene::Rect) -> gdk::RGBA {
+        let (width, height) = (self.width as f32, self.height as f32);
+
+        average_color(
+            &self.pixels,
+            self.width,
+            self.height,
+            4 * self.width as usize,
+            &graphene::Rect::new(
+                area.x() * width,
+                area.y() * height,
+                area.width() * width,
+                area.height() * height,
+            ),
+        )
+    }
+
+    /// Average alpha of the whole texture
+    pub(super) fn coverage(&self) -> f32 {
+        self.average_color(&graphene::Rect::new(0.0, 0.0, 1.0, 1.0))
+            .alpha()
+    }
+}
@@ -1,6 +1,12 @@
 // Based on Mikhail Filimonov's code from
 // https://github.com/overtake/TelegramSwift/blob/master/packages/TGUIKit/Sources/SoftwareGradientBackground.swift
 
+use std::sync::Arc;
+use std::sync::Mutex;
+
+use super::color_space::ColorSpace;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub(super) struct Color {
     r: f32,
     g: f32,
@@ -19,6 +25,23 @@ impl Color {
 
         Self::new(r as f32 / 255.0, g as f32 / 255.0, b as f32 / 255.0)
     }
+
+    pub(super) fn to_int_rgb(self) -> i32 {
+        let [r, g, b] = [self.r, self.g, self.b].map(|channel| (channel * 255.0).round() as i32);
+        (r << 16) | (g << 8) | b
+    }
+
+    pub(super) fn to_array(self) -> [f32; 3] {
+        [self.r, self.g, self.b]
+    }
+
+    pub(super) fn to_vec3(self) -> gtk::graphene::Vec3 {
+        gtk::graphene::Vec3::new(self.r, self.g, self.b)
+    }
+
+    pub(super) fn to_rgba(self) -> gtk::gdk::RGBA {
+        gtk::gdk::RGBA::new(self.r, self.g, self.b, 1.0)
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -66,46 +89,215 @@ pub(super) fn gather_positions(offset: usize) -> Vec<Point> {
         .collect()
 }
 
-/// Returns buffer for a texture with BGRA8 format.
+/// Pixel format of generated images
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum PixelFormat {
+    /// BGRA8 with ordered dithering, for images displayed at their own size
+    Bgra8,
+    /// RGBA with 16 bit float channels and without dithering, for small textures
+    /// stretched by the GPU, dithering patterns would be stretched with them
+    Rgba16Float,
+}
+
+impl PixelFormat {
+    pub(super) fn bytes_per_pixel(self) -> usize {
+        match self {
+            Self::Bgra8 => 4,
+            Self::Rgba16Float => 8,
+        }
+    }
+}
+
+/// 8x8 Bayer matrix for ordered dithering
+const BAYER_MATRIX: [[u8; 8]; 8] = [
+    [0, 32, 8, 40, 2, 34, 10, 42],
+    [48, 16, 56, 24, 50, 18, 58, 26],
+    [12, 44, 4, 36, 14, 46, 6, 38],
+    [60, 28, 52, 20, 62, 30, 54, 22],
+    [3, 35, 11, 43, 1, 33, 9, 41],
+    [51, 19, 59, 27, 49, 17, 57, 25],
+    [15, 47, 7, 39, 13, 45, 5, 37],
+    [63, 31, 55, 23, 61, 29, 53, 21],
+];
+
+/// Converts a channel to 8 bits with ordered dithering
+///
+/// Smooth gradients have visible bands in 8 bits, especially dark ones,
+/// the threshold of the pixel spreads the rounding error between neighbours.
+fn quantize(channel: f32, x: usize, y: usize) -> u8 {
+    let threshold = (BAYER_MATRIX[y % 8][x % 8] as f32 + 0.5) / 64.0;
+    (channel * 255.0 + threshold).clamp(0.0, 255.0) as u8
+}
+
+/// Converts a channel from 0 to 1 to the bits of a half float, rounding to the nearest value
+fn to_f16_bits(channel: f32) -> u16 {
+    if channel.is_nan() {
+        return 0;
+    }
+
+    let channel = channel.clamp(0.0, 1.0);
+
+    // Subnormal half floats are multiples of 2^-24
+    if channel < 2.0_f32.powi(-14) {
+        return (channel * 2.0_f32.powi(24)).round() as u16;
+    }
+
+    let bits = channel.to_bits();
+    let exponent = ((bits >> 23) & 0xff) + 15 - 127;
+    let mantissa = bits & 0x7f_ffff;
+
+    // Carry of the rounding moves to the exponent, which is still correct
+    ((exponent << 10 | mantissa >> 13) + (mantissa >> 12 & 1)) as u16
+}
+
+fn write_pixel(pixel: &mut [u8], color: Color, x: usize, y: usize, format: PixelFormat) {
+    match format {
+        PixelFormat::Bgra8 => {
+            pixel[0] = quantize(color.b, x, y);
+            pixel[1] = quantize(color.g, x, y);
+            pixel[2] = quantize(color.r, x, y);
+            pixel[3] = 255;
+        }
+        PixelFormat::Rgba16Float => {
+            let channels = [color.r, color.g, color.b, 1.0].map(to_f16_bits);
+            for (bytes, channel) in pixel.chunks_exact_mut(2).zip(channels) {
+                bytes.copy_from_slice(&channel.to_ne_bytes());
+            }
+        }
+    }
+}
+
+/// Maximum number of swirl tables kept in memory
+const SWIRL_TABLES_LIMIT: usize = 8;
+
+/// Swirled coordinates of every pixel for a given texture size
+///
+/// The swirl doesn't depend on colors or positions,
+/// so one table is shared by every frame with the same size.
+pub(super) struct SwirlTable {
+    width: u32,
+    height: u32,
+    xs: Box<[f32]>,
+    ys: Box<[f32]>,
+}
+
+impl SwirlTable {
+    fn new(width: u32, height: u32) -> Self {
+        let len = width as usize * height as usize;
+        let mut xs = Vec::with_capacity(len);
+        let mut ys = Vec::with_capacity(len);
+
+        for y in 0..height {
+            let direct_pixel_y = y as f32 / height as f32;
+            let center_distance_y = direct_pixel_y - 0.5;
+            let center_distance_y2 = center_distance_y * center_distance_y;
+
+            for x in 0..width {
+                // Every coordinate is normalized by its own dimension like in the shader
+                // and in the stretched square texture, TelegramSwift renders only square images
+                // where it's the same as dividing by the height
+                let direct_pixel_x = x as f32 / width as f32;
+                let center_distance_x = direct_pixel_x - 0.5;
+
+                let center_distance =
+                    (center_distance_x * center_distance_x + center_distance_y2).sqrt();
+
+                let swirl_factor = 0.35 * center_distance;
+                let theta = swirl_factor * swirl_factor * 0.8 * 8.0;
+
+                let (sin_theta, cos_theta) = theta.sin_cos();
+
+                xs.push(
+                    (0.5 + center_distance_x * cos_theta - center_distance_y * sin_theta)
+                        .clamp(0.0, 1.0),
+                );
+                ys.push(
+                    (0.5 + center_distance_x * sin_theta + center_distance_y * cos_theta)
+                        .clamp(0.0, 1.0),
+                );
+            }
+        }
+
+        Self {
+            width,
+            height,
+            xs: xs.into_boxed_slice(),
+            ys: ys.into_boxed_slice(),
+        }
+    }
+
+    /// Returns a cached table or computes a new one
+    pub(super) fn get(width: u32, height: u32) -> Arc<Self> {
+        static TABLES: Mutex<Vec<Arc<SwirlTable>>> = Mutex::new(Vec::new());
+
+        let mut tables = TABLES.lock().unwrap();
+
+        if let Some(index) = tables
+            .iter()
+            .position(|table| table.width == width && table.height == height)
+        {
+            // Move to the end, so the least recently used table is always first
+            let table = tables.remove(index);
+            tables.push(table.clone());
+            return table;
+        }
+
+        if tables.len() >= SWIRL_TABLES_LIMIT {
+            tables.remove(0);
+        }
+
+        let table = Arc::new(Self::new(width, height));
+        tables.push(table.clone());
+        table
+    }
+
+    fn row(&self, y: usize) -> (&[f32], &[f32]) {
+        let range = y * self.width as usize..(y + 1) * self.width as usize;
+        (&self.xs[range.clone()], &self.ys[range])
+    }
+}
+
+/// Returns buffer for a texture with the given format, BGRA8 output is dithered.
 #[doc(alias = "generateGradient")]
 pub(super) fn generate_gradient(
     width: u32,
     height: u32,
     colors: &[Color],
     positions: &[Point],
+    color_space: ColorSpace,
+    format: PixelFormat,
 ) -> Box<[u8]> {
-    let bytes_per_row = 4 * width as usize;
-    let mut image_bytes = vec![0u8; bytes_per_row * height as usize].into_boxed_slice();
-
-    for (y, row) in image_bytes.chunks_exact_mut(bytes_per_row).enumerate() {
-        let direct_pixel_y = y as f32 / height as f32;
-        let center_distance_y = direct_pixel_y - 0.5;
-        let center_distance_y2 = center_distance_y * center_distance_y;
-
-        for (x, pixel) in row.chunks_exact_mut(4).enumerate() {
-            let direct_pixel_x = x as f32 / height as f32;
-            let center_distance_x = direct_pixel_x - 0.5;
+    let swirl = SwirlTable::get(width, height);
 
-            let center_distance =
-                (center_distance_x * center_distance_x + center_distance_y2).sqrt();
+    let bytes_per_pixel = format.bytes_per_pixel();
+    let bytes_per_row = bytes_per_pixel * width as usize;
+    let mut image_bytes = vec![0u8; bytes_per_row * height as usize].into_boxed_slice();
 
-            let swirl_factor = 0.35 * center_distance;
-            let theta = swirl_factor * swirl_factor * 0.8 * 8.0;
+    // Accumulators for a single row, they are kept in separate arrays
+    // so the loops below can be vectorized by the compiler
+    let mut distance_sum = vec![0.0; width as usize];
+    let mut r = vec![0.0; width as usize];
+    let mut g = vec![0.0; width as usize];
+    let mut b = vec![0.0; width as usize];
 
-            // Note: sin_cos takes noticable amount of time.
-            // It's possible to get better performance
-            let (sin_theta, cos_theta) = theta.sin_cos();
+    for (y, row) in image_bytes.chunks_exact_mut(bytes_per_row).enumerate() {
+        let (xs, ys) = swirl.row(y);
 
-            let pixel_x = (0.5 + center_distance_x * cos_theta - center_distance_y * sin_theta)
-                .clamp(0.0, 1.0);
+        distance_sum.fill(0.0);
+        r.fill(0.0);
+        g.fill(0.0);
+        b.fill(0.0);
 
-            let pixel_y = (0.5 + center_distance_x * sin_theta + center_distance_y * cos_theta)
-                .clamp(0.0, 1.0);
+        for (&color, pos) in colors.iter().zip(positions.iter()) {
+            let [color_r, color_g, color_b] = color_space.encode(color);
 
-            let mut distance_sum = 0.0;
-            let (mut r, mut g, mut b) = (0.0, 0.0, 0.0);
+            let pixels = xs
+                .iter()
+                .zip(ys)
+                .zip(&mut distance_sum)
+                .zip(r.iter_mut().zip(&mut g).zip(&mut b));
 
-            for (color, pos) in colors.iter().zip(positions.iter()) {
+            for (((pixel_x, pixel_y), distance_sum), ((r, g), b)) in pixels {
                 let distance_x = pixel_x - pos.x;
                 let distance_y = pixel_y - pos.y;
 
@@ -113,18 +305,379 @@ pub(super) fn generate_gradient(
                     (0.92 - (distance_x * distance_x + distance_y * distance_y).sqrt()).max(0.0);
 
                 distance = distance * distance * distance;
-                distance_sum += distance;
+                *distance_sum += distance;
 
-                r += distance * color.r;
-                g += distance * color.g;
-                b += distance * color.b;
+                *r += distance * color_r;
+                *g += distance * color_g;
+                *b += distance * color_b;
             }
+        }
 
-            pixel[0] = (b / distance_sum * 255.0) as u8;
-            pixel[1] = (g / distance_sum * 255.0) as u8;
-            pixel[2] = (r / distance_sum * 255.0) as u8;
-            pixel[3] = 255;
+        let pixels = row
+            .chunks_exact_mut(bytes_per_pixel)
+            .zip(&distance_sum)
+            .zip(r.iter().zip(&g).zip(&b));
+
+        for (x, ((pixel, &distance_sum), ((r, g), b))) in pixels.enumerate() {
+            let color = if distance_sum > 0.0 {
+                color_space.decode([r / distance_sum, g / distance_sum, b / distance_sum])
+            } else {
+                // Custom points can all be too far from the pixel, it takes the nearest color
+                nearest_color(xs[x], ys[x], colors, positions)
+            };
+
+            write_pixel(pixel, color, x, y, format);
+        }
+    }
+    image_bytes
+}
+
+/// Returns the color of the point closest to the pixel
+fn nearest_color(pixel_x: f32, pixel_y: f32, colors: &[Color], positions: &[Point]) -> Color {
+    colors
+        .iter()
+        .zip(positions)
+        .map(|(&color, pos)| {
+            let (distance_x, distance_y) = (pixel_x - pos.x, pixel_y - pos.y);
+            (color, distance_x * distance_x + distance_y * distance_y)
+        })
+        .min_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map(|(color, _)| color)
+        .unwrap_or(Color::new(0.0, 0.0, 0.0))
+}
+
+/// Returns buffer for a texture with the given format filled with one color.
+pub(super) fn generate_solid(
+    width: u32,
+    height: u32,
+    color: Color,
+    format: PixelFormat,
+) -> Box<[u8]> {
+    let pixel = match format {
+        PixelFormat::Bgra8 => vec![
+            (color.b * 255.0) as u8,
+            (color.g * 255.0) as u8,
+            (color.r * 255.0) as u8,
+            255,
+        ],
+        PixelFormat::Rgba16Float => [color.r, color.g, color.b, 1.0]
+            .map(to_f16_bits)
+            .iter()
+            .flat_map(|channel| channel.to_ne_bytes())
+            .collect(),
+    };
+
+    pixel
+        .repeat(width as usize * height as usize)
+        .into_boxed_slice()
+}
+
+/// Returns start and end points of a linear gradient in normalized coordinates
+///
+/// Rotation is in degrees like in Telegram wallpaper settings,
+/// 0 means that the first color is at the top and it goes clockwise.
+/// Diagonal gradients go from corner to corner.
+pub(super) fn linear_gradient_line(rotation: i32) -> (Point, Point) {
+    let (sin, cos) = (rotation as f32).to_radians().sin_cos();
+    let (direction_x, direction_y) = (-sin, cos);
+
+    let half_length = 0.5 * (direction_x.abs() + direction_y.abs());
+
+    let start = Point {
+        x: 0.5 - direction_x * half_length,
+        y: 0.5 - direction_y * half_length,
+    };
+    let end = Point {
+        x: 0.5 + direction_x * half_length,
+        y: 0.5 + direction_y * half_length,
+    };
+
+    (start, end)
+}
+
+/// Returns buffer for a texture with the given format with a two color linear gradient,
+/// BGRA8 output is dithered.
+///
+/// Pixels are projected on the line in pixel space like GSK does,
+/// so diagonal gradients of non-square images still go from corner to corner.
+pub(super) fn generate_linear_gradient(
+    width: u32,
+    height: u32,
+    colors: [Color; 2],
+    rotation: i32,
+    color_space: ColorSpace,
+    format: PixelFormat,
+) -> Box<[u8]> {
+    let (start, end) = linear_gradient_line(rotation);
+    let (start_x, start_y) = (start.x * width as f32, start.y * height as f32);
+    let (line_x, line_y) = (
+        (end.x - start.x) * width as f32,
+        (end.y - start.y) * height as f32,
+    );
+    let line_length2 = line_x * line_x + line_y * line_y;
+
+    let bytes_per_pixel = format.bytes_per_pixel();
+    let bytes_per_row = bytes_per_pixel * width as usize;
+    let mut image_bytes = vec![0u8; bytes_per_row * height as usize].into_boxed_slice();
+
+    for (y, row) in image_bytes.chunks_exact_mut(bytes_per_row).enumerate() {
+        let pixel_y = y as f32 + 0.5;
+
+        for (x, pixel) in row.chunks_exact_mut(bytes_per_pixel).enumerate() {
+            let pixel_x = x as f32 + 0.5;
+
+            let factor = (((pixel_x - start_x) * line_x + (pixel_y - start_y) * line_y)
+                / line_length2)
+                .clamp(0.0, 1.0);
+
+            let color = color_space.interpolate(colors[0], colors[1], factor);
+
+            write_pixel(pixel, color, x, y, format);
         }
     }
     image_bytes
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Per-pixel implementation that was used before the swirl tables
+    ///
+    /// x is normalized by the width, which matches TelegramSwift for its square images
+    fn reference_gradient(
+        width: u32,
+        height: u32,
+        colors: &[Color],
+        positions: &[Point],
+    ) -> Box<[u8]> {
+        let bytes_per_row = 4 * width as usize;
+        let mut image_bytes = vec![0u8; bytes_per_row * height as usize].into_boxed_slice();
+
+        for (y, row) in image_bytes.chunks_exact_mut(bytes_per_row).enumerate() {
+            let direct_pixel_y = y as f32 / height as f32;
+            let center_distance_y = direct_pixel_y - 0.5;
+            let center_distance_y2 = center_distance_y * center_distance_y;
+
+            for (x, pixel) in row.chunks_exact_mut(4).enumerate() {
+                let direct_pixel_x = x as f32 / width as f32;
+                let center_distance_x = direct_pixel_x - 0.5;
+
+                let center_distance =
+                    (center_distance_x * center_distance_x + center_distance_y2).sqrt();
+
+                let swirl_factor = 0.35 * center_distance;
+                let theta = swirl_factor * swirl_factor * 0.8 * 8.0;
+
+                let (sin_theta, cos_theta) = theta.sin_cos();
+
+                let pixel_x = (0.5 + center_distance_x * cos_theta - center_distance_y * sin_theta)
+                    .clamp(0.0, 1.0);
+
+                let pixel_y = (0.5 + center_distance_x * sin_theta + center_distance_y * cos_theta)
+                    .clamp(0.0, 1.0);
+
+                let mut distance_sum = 0.0;
+                let (mut r, mut g, mut b) = (0.0, 0.0, 0.0);
+
+                for (color, pos) in colors.iter().zip(positions.iter()) {
+                    let distance_x = pixel_x - pos.x;
+                    let distance_y = pixel_y - pos.y;
+
+                    let mut distance = (0.92
+                        - (distance_x * distance_x + distance_y * distance_y).sqrt())
+                    .max(0.0);
+
+                    distance = distance * distance * distance;
+                    distance_sum += distance;
+
+                    r += distance * color.r;
+                    g += distance * color.g;
+                    b += distance * color.b;
+                }
+
+                pixel[0] = (b / distance_sum * 255.0) as u8;
+                pixel[1] = (g / distance_sum * 255.0) as u8;
+                pixel[2] = (r / distance_sum * 255.0) as u8;
+                pixel[3] = 255;
+            }
+        }
+        image_bytes
+    }
+
+    #[test]
+    fn swirl_table_matches_per_pixel_loop() {
+        let colors = [0xdbddbb, 0x6ba587, 0xd5d88d, 0x88b884].map(Color::from_int_rgb);
+
+        for offset in [0, 3] {
+            let positions = gather_positions(offset);
+
+            for (width, height) in [(1, 1), (16, 16), (64, 64), (60, 40), (17, 93)] {
+                let expected = reference_gradient(width, height, &colors, &positions);
+                let actual = generate_gradient(
+                    width,
+                    height,
+                    &colors,
+                    &positions,
+                    ColorSpace::Srgb,
+                    PixelFormat::Bgra8,
+                );
+
+                assert_eq!(expected.len(), actual.len());
+
+                for (index, (expected, actual)) in expected.iter().zip(actual.iter()).enumerate() {
+                    assert!(
+                        expected.abs_diff(*actual) <= 1,
+                        "{width}x{height}, byte {index}: expected {expected}, got {actual}"
+                    );
+                }
+            }
+        }
+    }
+
+    /// Returns the widest run of columns that look the same in every row
+    fn widest_band(buffer: &[u8], width: usize, bytes_per_pixel: usize) -> usize {
+        let rows: Vec<_> = buffer.chunks_exact(width * bytes_per_pixel).collect();
+        let columns: Vec<Vec<_>> = (0..width)
+            .map(|x| {
+                rows.iter()
+                    .map(|row| &row[x * bytes_per_pixel..(x + 1) * bytes_per_pixel])
+                    .collect()
+            })
+            .collect();
+
+        columns
+            .chunk_by(|a, b| a == b)
+            .map(<[_]>::len)
+            .max()
+            .unwrap_or(0)
+    }
+
+    #[test]
+    fn no_large_uniform_bands_along_ramp() {
+        // 16 levels of a dark ramp over 256 pixels, rounding would make bands of 16 pixels
+        let colors = [0x101010, 0x202020].map(Color::from_int_rgb);
+        let (width, height) = (256, 8);
+
+        for format in [PixelFormat::Bgra8, PixelFormat::Rgba16Float] {
+            let buffer =
+                generate_linear_gradient(width, height, colors, 90, ColorSpace::Srgb, format);
+
+            let band = widest_band(&buffer, width as usize, format.bytes_per_pixel());
+            assert!(band <= 2, "{format:?}: band of {band} pixels");
+        }
+    }
+
+    #[test]
+    fn half_float_conversion() {
+        assert_eq!(to_f16_bits(0.0), 0);
+        assert_eq!(to_f16_bits(1.0), 0x3c00);
+        assert_eq!(to_f16_bits(0.5), 0x3800);
+        assert_eq!(to_f16_bits(2.0_f32.powi(-14)), 0x0400);
+        assert_eq!(to_f16_bits(2.0_f32.powi(-24)), 0x0001);
+        assert_eq!(to_f16_bits(1.0 / 3.0), 0x3555);
+        assert_eq!(to_f16_bits(-1.0), 0);
+        assert_eq!(to_f16_bits(2.0), 0x3c00);
+    }
+
+    #[test]
+    fn linear_gradient_line_directions() {
+        let line = |rotation| {
+            let (start, end) = linear_gradient_line(rotation);
+            [start.x, start.y, end.x, end.y].map(|value| (value * 1000.0).round() / 1000.0)
+        };
+
+        // The first color is at the top and the gradient goes clockwise
+        assert_eq!(line(0), [0.5, 0.0, 0.5, 1.0]);
+        assert_eq!(line(45), [1.0, 0.0, 0.0, 1.0]);
+        assert_eq!(line(90), [1.0, 0.5, 0.0, 0.5]);
+        assert_eq!(line(180), [0.5, 1.0, 0.5, 0.0]);
+        assert_eq!(line(315), [0.0, 0.0, 1.0, 1.0]);
+    }
+
+    #[test]
+    fn linear_gradient_projects_in_pixel_space() {
+        let colors = [0x000000, 0xffffff].map(Color::from_int_rgb);
+        let (width, height) = (200, 100);
+
+        let buffer = generate_linear_gradient(
+            width,
+            height,
+            colors,
+            45,
+            ColorSpace::Srgb,
+            PixelFormat::Bgra8,
+        );
+        let pixel = |x: u32, y: u32| buffer[4 * (y * width + x) as usize] as f32;
+
+        // Corners of the line have the colors
+        assert!(pixel(width - 1, 0) <= 3.0);
+        assert!(pixel(0, height - 1) >= 252.0);
+
+        // Like in GSK the other corners are projected on the line in pixels,
+        // so the top left corner is at w² / (w² + h²) of the way
+        let (w, h) = (width as f32, height as f32);
+        let expected = 255.0 * w * w / (w * w + h * h);
+        assert!((pixel(0, 0) - expected).abs() <= 3.0);
+        assert!((pixel(width - 1, height - 1) - (255.0 - expected)).abs() <= 3.0);
+    }
+
+    #[test]
+    fn far_custom_points_have_no_gaps() {
+        let red = Color::from_int_rgb(0xff0000);
+        let blue = Color::from_int_rgb(0x0000ff);
+        let (width, height) = (32, 32);
+
+        // The opposite corners are more than 0.92 away from every point
+        let cases: [(&[Color], &[Point]); 2] = [
+            (&[red], &[Point { x: 0.0, y: 0.0 }]),
+            (
+                &[red, blue],
+                &[Point { x: 0.0, y: 0.0 }, Point { x: 1.0, y: 0.0 }],
+            ),
+        ];
+
+        for (colors, positions) in cases {
+            let buffer = generate_gradient(
+                width,
+                height,
+                colors,
+                positions,
+                ColorSpace::Srgb,
+                PixelFormat::Bgra8,
+            );
+            for pixel in buffer.chunks_exact(4) {
+                assert!(pixel[0] > 0 || pixel[2] > 0, "black pixel {pixel:?}");
+            }
+
+            let buffer = generate_gradient(
+                width,
+                height,
+                colors,
+                positions,
+                ColorSpace::Srgb,
+                PixelFormat::Rgba16Float,
+            );
+            for channel in buffer.chunks_exact(2) {
+                let bits = u16::from_ne_bytes([channel[0], channel[1]]);
+                assert!(bits <= 0x3c00, "channel out of range {bits:#x}");
+            }
+        }
+
+        // A single point fills the far corner with its color
+        let buffer = generate_gradient(
+            width,
+            height,
+            &[red],
+            &[Point { x: 0.0, y: 0.0 }],
+            ColorSpace::Srgb,
+            PixelFormat::Bgra8,
+        );
+        assert_eq!(buffer[buffer.len() - 4..], [0, 0, 255, 255]);
+    }
+
+    #[test]
+    fn half_float_of_nan_is_zero() {
+        assert_eq!(to_f16_bits(f32::NAN), 0);
+    }
+}
@@ -0,0 +1,284 @@
+use std::cell::Cell;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use gtk::gdk;
+use gtk::gio;
+use gtk::glib;
+
+use super::software_gradient::PixelFormat;
+use super::GradientRenderer;
+
+/// Maximum number of textures shared between all gradients of the thread
+const TEXTURES_LIMIT: usize = 64;
+
+/// Cached textures are small and stretched, so they keep the full precision instead of dithering
+const FORMAT: PixelFormat = PixelFormat::Rgba16Float;
+
+struct CachedTexture {
+    renderer: GradientRenderer,
+    width: u32,
+    height: u32,
+    texture: gdk::MemoryTexture,
+}
+
+impl CachedTexture {
+    fn matches(&self, renderer: &GradientRenderer, width: u32, height: u32) -> bool {
+        self.width == width && self.height == height && self.renderer == *renderer
+    }
+}
+
+/// Texture that is being rendered and callbacks waiting for it
+struct PendingTexture {
+    renderer: GradientRenderer,
+    width: u32,
+    height: u32,
+    callbacks: Vec<Box<dyn FnOnce(Option<gdk::MemoryTexture>)>>,
+}
+
+impl PendingTexture {
+    fn matches(&self, renderer: &GradientRenderer, width: u32, height: u32) -> bool {
+        self.width == width && self.height == height && self.renderer == *renderer
+    }
+}
+
+thread_local! {
+    static TEXTURES: RefCell<Vec<CachedTexture>> = const { RefCell::new(Vec::new()) };
+    static PENDING: RefCell<Vec<PendingTexture>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Returns a texture for the current state of the renderer
+///
+/// Textures for rest phases are cached, so gradients with the same colors
+/// are rendered only once, intermediate animation frames are always rendered.
+pub(super) fn texture(renderer: &GradientRenderer, width: u32, height: u32) -> gdk::MemoryTexture {
+    if let Some(texture) = cached(renderer, width, height) {
+        return texture;
+    }
+
+    let buffer = renderer.render_pixels(width, height, FORMAT);
+    let texture = GradientRenderer::texture_from_buffer(width, height, buffer, FORMAT);
+    insert(renderer, width, height, &texture);
+    texture
+}
+
+/// Renders the texture on the thread pool of gio and passes it to `callback`
+///
+/// Requests for the same state share one rendering, so gradients following
+/// the same widget don't render the same animation frame again.
+/// The texture is `None` if the rendering thread panicked.
+pub(super) fn request(
+    renderer: GradientRenderer,
+    width: u32,
+    height: u32,
+    callback: impl FnOnce(Option<gdk::MemoryTexture>) + 'static,
+) {
+    if let Some(texture) = cached(&renderer, width, height) {
+        callback(Some(texture));
+        return;
+    }
+
+    let callback: Box<dyn FnOnce(Option<gdk::MemoryTexture>)> = Box::new(callback);
+
+    let is_pending = PENDING.with_borrow_mut(|pending| {
+        match pending
+            .iter_mut()
+            .find(|pending| pending.matches(&renderer, width, height))
+        {
+            Some(pending) => {
+                pending.callbacks.push(callback);
+                true
+            }
+            None => {
+                pending.push(PendingTexture {
+                    renderer: renderer.clone(),
+                    width,
+                    height,
+                    callbacks: vec![callback],
+                });
+                false
+            }
+        }
+    });
+
+    if is_pending {
+        return;
+    }
+
+    glib::spawn_future_local(async move {
+        let texture = render(renderer.clone(), width, height).await;
+
+        let callbacks = PENDING.with_borrow_mut(|pending| {
+            pending
+                .iter()
+                .position(|pending| pending.matches(&renderer, width, height))
+                .map(|index| pending.remove(index).callbacks)
+                .unwrap_or_default()
+        });
+
+        for callback in callbacks {
+            callback(texture.clone());
+        }
+    });
+}
+
+/// Renders the texture on the thread pool of gio
+///
+/// Like with [texture], rest phases are taken from the cache or stored there.
+/// Returns `None` if the rendering thread panicked.
+async fn render(renderer: GradientRenderer, width: u32, height: u32) -> Option<gdk::MemoryTexture> {
+    if let Some(texture) = cached(&renderer, width, height) {
+        return Some(texture);
+    }
+
+    let buffer = gio::spawn_blocking({
+        let renderer = renderer.clone();
+        move || renderer.render_pixels(width, height, FORMAT)
+    })
+    .await;
+
+    let Ok(buffer) = buffer else {
+        log::error!("gradient rendering thread panicked");
+        return None;
+    };
+
+    let texture = GradientRenderer::texture_from_buffer(width, height, buffer, FORMAT);
+    insert(&renderer, width, height, &texture);
+    Some(texture)
+}
+
+/// Returns the cached texture of a rest phase
+pub(super) fn cached(
+    renderer: &GradientRenderer,
+    width: u32,
+    height: u32,
+) -> Option<gdk::MemoryTexture> {
+    TEXTURES.with_borrow_mut(|textures| {
+        let index = textures
+            .iter()
+            .position(|cached| cached.matches(renderer, width, height))?;
+
+        // Move to the end, so the least recently used texture is always first
+        let cached = textures.remove(index);
+        let texture = cached.texture.clone();
+        textures.push(cached);
+        Some(texture)
+    })
+}
+
+/// Stores textures of rest phases, intermediate animation frames are ignored
+///
+/// Concurrent [render] calls can finish the same texture twice, only the first one is kept.
+fn insert(renderer: &GradientRenderer, width: u32, height: u32, texture: &gdk::MemoryTexture) {
+    if renderer.progress() != 0.0 {
+        return;
+    }
+
+    TEXTURES.with_borrow_mut(|textures| {
+        if textures
+            .iter()
+            .any(|cached| cached.matches(renderer, width, height))
+        {
+            return;
+        }
+
+        if textures.len() >= TEXTURES_LIMIT {
+            textures.remove(0);
+        }
+
+        textures.push(CachedTexture {
+            renderer: renderer.clone(),
+            width,
+            height,
+            texture: texture.clone(),
+        });
+    })
+}
+
+#[derive(Default)]
+struct AnimatedTextureState {
+    last: RefCell<Option<(GradientRenderer, gdk::MemoryTexture)>>,
+    rendering: Cell<bool>,
+}
+
+/// Texture of a gradient that changes during animations
+///
+/// States that aren't cached are rendered in background and the last texture is shown meanwhile,
+/// so animation frames don't block drawing.
+#[derive(Default)]
+pub(crate) struct AnimatedTexture(Rc<AnimatedTextureState>);
+
+impl AnimatedTexture {
+    /// Returns the texture for the state of the renderer or the last rendered one
+    ///
+    /// `redraw` is called when the texture of the state is ready.
+    pub(super) fn texture(
+        &self,
+        renderer: &GradientRenderer,
+        width: u32,
+        height: u32,
+        redraw: impl FnOnce() + 'static,
+    ) -> gdk::MemoryTexture {
+        if let Some(texture) = cached(renderer, width, height) {
+            self.0
+                .last
+                .replace(Some((renderer.clone(), texture.clone())));
+            return texture;
+        }
+
+        let last = self.0.last.borrow().clone();
+
+        match last {
+            Some((last_renderer, texture)) if last_renderer == *renderer => texture,
+            Some((_, texture)) => {
+                self.render(renderer.clone(), width, height, redraw);
+                texture
+            }
+            // There is nothing to show yet, so the first texture is rendered right away
+            None => self.prepare(renderer, width, height),
+        }
+    }
+
+    /// Renders the texture for the state of the renderer right away
+    pub(super) fn prepare(
+        &self,
+        renderer: &GradientRenderer,
+        width: u32,
+        height: u32,
+    ) -> gdk::MemoryTexture {
+        let texture = texture(renderer, width, height);
+        self.0
+            .last
+            .replace(Some((renderer.clone(), texture.clone())));
+        texture
+    }
+
+    fn render(
+        &self,
+        renderer: GradientRenderer,
+        width: u32,
+        height: u32,
+        redraw: impl FnOnce() + 'static,
+    ) {
+        // When the texture is ready the widget is redrawn
+        // and the latest state is requested, so intermediate states are skipped
+        if self.0.rendering.replace(true) {
+            return;
+        }
+
+        let state = Rc::downgrade(&self.0);
+
+        request(renderer.clone(), width, height, move |texture| {
+            let Some(state) = state.upgrade() else {
+                return;
+            };
+
+            state.rendering.set(false);
+
+            if let Some(texture) = texture {
+                state.last.replace(Some((renderer, texture)));
+                redraw();
+            }
+        });
+    }
+}
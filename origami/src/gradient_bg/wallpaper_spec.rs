@@ -0,0 +1,525 @@
+use std::fmt;
+use std::str::FromStr;
+
+use gtk::gdk;
+
+use super::GradientBg;
+use super::DEFAULT_INTENSITY;
+use super::MAX_COLORS;
+
+/// Telegram wallpaper settings
+///
+/// Can be parsed from background links and slugs like
+/// `t.me/bg/<slug>?bg_color=aaaaaa~bbbbbb~cccccc~dddddd&intensity=-40&mode=motion`,
+/// `t.me/bg/aaaaaa-bbbbbb?rotation=45` or `tg://bg?color=aaaaaa`.
+/// [to_link](Self::to_link) produces a `https://t.me/bg/` link.
+///
+/// `WallPaperSettings` objects are converted with [from_settings](Self::from_settings).
+///
+/// # Example
+/// ```no_run
+/// # let gradient_bg = origami::GradientBg::new();
+/// let spec: origami::WallpaperSpec = "t.me/bg/aaaaaa~bbbbbb~cccccc~dddddd".parse().unwrap();
+/// spec.apply(&gradient_bg);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WallpaperSpec {
+    /// Slug of the pattern or the image, the file itself should be downloaded separately
+    pub slug: Option<String>,
+    /// Int colors like in themes returned by tdlib
+    pub colors: Vec<i32>,
+    /// Pattern intensity from -100 to 100
+    pub intensity: Option<i32>,
+    /// Rotation of two color gradients in degrees, multiple of 45
+    pub rotation: i32,
+    pub blur: bool,
+    pub motion: bool,
+}
+
+/// Fields of Telegram `WallPaperSettings`
+///
+/// Colors are ints like in themes returned by tdlib, missing colors are [None].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WallpaperSettings {
+    pub background_color: Option<i32>,
+    pub second_background_color: Option<i32>,
+    pub third_background_color: Option<i32>,
+    pub fourth_background_color: Option<i32>,
+    pub intensity: Option<i32>,
+    pub rotation: Option<i32>,
+    pub blur: bool,
+    pub motion: bool,
+}
+
+/// Error returned when parsing or validating a [WallpaperSpec]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WallpaperSpecError {
+    /// The string has no slug and no colors
+    Empty,
+    /// The link doesn't point to a background
+    InvalidLink(String),
+    InvalidSlug(String),
+    /// Colors should be 6 hex digits
+    InvalidColor(String),
+    /// Wallpapers can have from 1 to 4 colors
+    InvalidColorCount(usize),
+    /// Intensity should be an integer from -100 to 100
+    InvalidIntensity(String),
+    /// Rotation should be an integer multiple of 45
+    InvalidRotation(String),
+}
+
+impl fmt::Display for WallpaperSpecError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Empty => write!(f, "wallpaper has neither slug nor colors"),
+            Self::InvalidLink(link) => write!(f, "not a background link: {link}"),
+            Self::InvalidSlug(slug) => write!(f, "invalid wallpaper slug: {slug}"),
+            Self::InvalidColor(color) => write!(f, "invalid wallpaper color: {color}"),
+            Self::InvalidColorCount(count) => {
+                write!(
+                    f,
+                    "wallpaper should have 1 to {MAX_COLORS} colors, got {count}"
+                )
+            }
+            Self::InvalidIntensity(intensity) => {
+                write!(f, "invalid wallpaper intensity: {intensity}")
+            }
+            Self::InvalidRotation(rotation) => write!(f, "invalid wallpaper rotation: {rotation}"),
+        }
+    }
+}
+
+impl std::error::Error for WallpaperSpecError {}
+
+impl WallpaperSpec {
+    /// Creates a spec from the slug of a wallpaper and its settings
+    pub fn from_settings(
+        slug: Option<&str>,
+        settings: &WallpaperSettings,
+    ) -> Result<Self, WallpaperSpecError> {
+        let colors = [
+            settings.background_color,
+            settings.second_background_color,
+            settings.third_background_color,
+            settings.fourth_background_color,
+        ];
+
+        // Colors go one after another, so the list stops at the first missing one
+        let colors = colors.into_iter().map_while(|color| color).collect();
+
+        let spec = Self {
+            slug: slug.map(str::to_owned),
+            colors,
+            intensity: settings.intensity,
+            rotation: settings.rotation.unwrap_or_default().rem_euclid(360),
+            blur: settings.blur,
+            motion: settings.motion,
+        };
+
+        spec.validate()?;
+
+        Ok(spec)
+    }
+
+    /// Checks that the spec can be applied and converted to a link
+    pub fn validate(&self) -> Result<(), WallpaperSpecError> {
+        if let Some(slug) = &self.slug {
+            parse_slug(slug)?;
+        } else if self.colors.is_empty() {
+            return Err(WallpaperSpecError::Empty);
+        }
+
+        if self.colors.len() > MAX_COLORS {
+            return Err(WallpaperSpecError::InvalidColorCount(self.colors.len()));
+        }
+
+        if let Some(intensity) = self.intensity.filter(|i| !(-100..=100).contains(i)) {
+            return Err(WallpaperSpecError::InvalidIntensity(intensity.to_string()));
+        }
+
+        if self.rotation % 45 != 0 {
+            return Err(WallpaperSpecError::InvalidRotation(
+                self.rotation.to_string(),
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Returns a `https://t.me/bg/` link that can be parsed back
+    ///
+    /// Fails if the spec isn't [valid](Self::validate).
+    pub fn to_link(&self) -> Result<String, WallpaperSpecError> {
+        self.validate()?;
+        Ok(self.to_string())
+    }
+
+    /// Applies colors, intensity and other settings to the widget
+    ///
+    /// Wallpapers without slug don't have a pattern or a photo, so they are removed,
+    /// otherwise the current ones are kept and the caller is expected to load
+    /// the file for the slug with [GradientBg::set_pattern_file] or [GradientBg::set_photo].
+    pub fn apply(&self, gradient_bg: &GradientBg) {
+        if !self.colors.is_empty() {
+            gradient_bg.set_theme_colors(&self.colors);
+        }
+
+        gradient_bg.set_intensity(self.intensity.unwrap_or(DEFAULT_INTENSITY));
+        gradient_bg.set_rotation(self.rotation);
+        gradient_bg.set_motion(self.motion);
+        gradient_bg.set_blur(self.blur);
+
+        if self.slug.is_none() {
+            gradient_bg.set_pattern(gdk::Texture::NONE);
+            gradient_bg.set_photo(gdk::Texture::NONE);
+        }
+    }
+
+    fn parse_link(link: &str) -> Result<Self, WallpaperSpecError> {
+        let (path, query) = link.split_once('?').unwrap_or((link, ""));
+
+        let mut spec = Self::default();
+
+        if let Some(path) = path.strip_prefix("tg://bg") {
+            if !path.is_empty() && path != "/" {
+                return Err(WallpaperSpecError::InvalidLink(link.to_owned()));
+            }
+        } else {
+            let path = path
+                .trim_start_matches("https://")
+                .trim_start_matches("http://");
+
+            let path = ["t.me/bg/", "telegram.me/bg/", "telegram.dog/bg/"]
+                .into_iter()
+                .find_map(|prefix| path.strip_prefix(prefix))
+                .unwrap_or(path)
+                .trim_end_matches('/');
+
+            if path.contains('/') {
+                return Err(WallpaperSpecError::InvalidLink(link.to_owned()));
+            }
+
+            if path.is_empty() {
+                // Only query parameters, they are checked below
+            } else if is_color_list(path) {
+                spec.colors = parse_colors(path)?;
+            } else {
+                spec.slug = Some(parse_slug(path)?);
+            }
+        }
+
+        for (key, value) in query
+            .split('&')
+            .filter(|pair| !pair.is_empty())
+            .map(|pair| pair.split_once('=').unwrap_or((pair, "")))
+        {
+            let value = decode_query_value(value);
+
+            match key {
+                "slug" => spec.slug = Some(parse_slug(&value)?),
+                "bg_color" | "color" | "gradient" => spec.colors = parse_colors(&value)?,
+                "intensity" => spec.intensity = Some(parse_intensity(&value)?),
+                "rotation" => spec.rotation = parse_rotation(&value)?,
+                "mode" => {
+                    for mode in value.split_whitespace() {
+                        match mode {
+                            "blur" => spec.blur = true,
+                            "motion" => spec.motion = true,
+                            // Unknown modes are ignored like in official clients
+                            _ => {}
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        spec.validate()?;
+
+        Ok(spec)
+    }
+}
+
+impl FromStr for WallpaperSpec {
+    type Err = WallpaperSpecError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if s.is_empty() {
+            return Err(WallpaperSpecError::Empty);
+        }
+
+        Self::parse_link(s)
+    }
+}
+
+/// Link without validation, it's used by [WallpaperSpec::to_link]
+impl fmt::Display for WallpaperSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut params = Vec::new();
+
+        match &self.slug {
+            Some(slug) => {
+                write!(f, "https://t.me/bg/{slug}")?;
+
+                if !self.colors.is_empty() {
+                    params.push(format!("bg_color={}", format_colors(&self.colors)));
+                }
+                if let Some(intensity) = self.intensity {
+                    params.push(format!("intensity={intensity}"));
+                }
+            }
+            None => write!(f, "https://t.me/bg/{}", format_colors(&self.colors))?,
+        }
+
+        if self.colors.len() == 2 && self.rotation != 0 {
+            params.push(format!("rotation={}", self.rotation));
+        }
+
+        let modes: Vec<_> = [(self.blur, "blur"), (self.motion, "motion")]
+            .into_iter()
+            .filter_map(|(enabled, mode)| enabled.then_some(mode))
+            .collect();
+
+        if !modes.is_empty() {
+            params.push(format!("mode={}", modes.join("+")));
+        }
+
+        if !params.is_empty() {
+            write!(f, "?{}", params.join("&"))?;
+        }
+
+        Ok(())
+    }
+}
+
+fn is_color(s: &str) -> bool {
+    s.len() == 6 && s.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+fn is_color_list(s: &str) -> bool {
+    !s.is_empty() && s.split(['-', '~']).all(is_color)
+}
+
+fn parse_colors(s: &str) -> Result<Vec<i32>, WallpaperSpecError> {
+    let colors = s
+        .split(['-', '~'])
+        .map(|color| {
+            // from_str_radix accepts a sign, so digits are checked first
+            if is_color(color) {
+                i32::from_str_radix(color, 16).ok()
+            } else {
+                None
+            }
+            .ok_or_else(|| WallpaperSpecError::InvalidColor(color.to_owned()))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    if colors.len() > MAX_COLORS {
+        return Err(WallpaperSpecError::InvalidColorCount(colors.len()));
+    }
+
+    Ok(colors)
+}
+
+fn format_colors(colors: &[i32]) -> String {
+    let separator = if colors.len() == 2 { "-" } else { "~" };
+
+    colors
+        .iter()
+        .map(|color| format!("{:06x}", color & 0xffffff))
+        .collect::<Vec<_>>()
+        .join(separator)
+}
+
+fn parse_slug(s: &str) -> Result<String, WallpaperSpecError> {
+    if !s.is_empty()
+        && s.chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        Ok(s.to_owned())
+    } else {
+        Err(WallpaperSpecError::InvalidSlug(s.to_owned()))
+    }
+}
+
+fn parse_intensity(s: &str) -> Result<i32, WallpaperSpecError> {
+    s.parse()
+        .ok()
+        .filter(|intensity| (-100..=100).contains(intensity))
+        .ok_or_else(|| WallpaperSpecError::InvalidIntensity(s.to_owned()))
+}
+
+fn parse_rotation(s: &str) -> Result<i32, WallpaperSpecError> {
+    s.parse::<i32>()
+        .ok()
+        .filter(|rotation| rotation % 45 == 0)
+        .map(|rotation| rotation.rem_euclid(360))
+        .ok_or_else(|| WallpaperSpecError::InvalidRotation(s.to_owned()))
+}
+
+/// Decodes `+` and percent-encoded characters of a query value
+fn decode_query_value(s: &str) -> String {
+    let mut bytes = Vec::with_capacity(s.len());
+    let mut iter = s.bytes();
+
+    while let Some(byte) = iter.next() {
+        match byte {
+            b'+' => bytes.push(b' '),
+            b'%' => {
+                let hex: Vec<_> = iter.clone().take(2).collect();
+                match std::str::from_utf8(&hex)
+                    .ok()
+                    .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                {
+                    Some(decoded) if hex.len() == 2 => {
+                        bytes.push(decoded);
+                        iter.nth(1);
+                    }
+                    _ => bytes.push(b'%'),
+                }
+            }
+            byte => bytes.push(byte),
+        }
+    }
+
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let links = [
+            "https://t.me/bg/aaaaaa",
+            "https://t.me/bg/aaaaaa-bbbbbb?rotation=45",
+            "https://t.me/bg/aaaaaa~bbbbbb~cccccc~dddddd?mode=motion",
+            "https://t.me/bg/pattern_slug?bg_color=aaaaaa~bbbbbb~cccccc&intensity=-40",
+            "https://t.me/bg/photo-slug?mode=blur+motion",
+        ];
+
+        for link in links {
+            let spec: WallpaperSpec = link.parse().unwrap();
+            assert_eq!(spec.to_link().unwrap(), link);
+            assert_eq!(spec.to_link().unwrap().parse::<WallpaperSpec>(), Ok(spec));
+        }
+    }
+
+    #[test]
+    fn parse_variants() {
+        let spec: WallpaperSpec = "tg://bg?slug=abc&color=ffffff&intensity=50&mode=motion"
+            .parse()
+            .unwrap();
+
+        assert_eq!(
+            spec,
+            WallpaperSpec {
+                slug: Some("abc".to_owned()),
+                colors: vec![0xffffff],
+                intensity: Some(50),
+                rotation: 0,
+                blur: false,
+                motion: true,
+            }
+        );
+
+        let spec: WallpaperSpec = "telegram.me/bg/000000-ffffff?rotation=-45".parse().unwrap();
+        assert_eq!(spec.colors, [0x000000, 0xffffff]);
+        assert_eq!(spec.rotation, 315);
+    }
+
+    #[test]
+    fn malformed_input() {
+        let cases = [
+            ("", WallpaperSpecError::Empty),
+            ("https://t.me/bg/", WallpaperSpecError::Empty),
+            ("tg://bg?intensity=10", WallpaperSpecError::Empty),
+            (
+                "https://example.com/bg/abc/def",
+                WallpaperSpecError::InvalidLink("https://example.com/bg/abc/def".to_owned()),
+            ),
+            (
+                "tg://bg?color=+abcde",
+                WallpaperSpecError::InvalidColor(" abcde".to_owned()),
+            ),
+            (
+                "tg://bg?color=%2Babcde",
+                WallpaperSpecError::InvalidColor("+abcde".to_owned()),
+            ),
+            (
+                "tg://bg?color=-abcde",
+                WallpaperSpecError::InvalidColor("".to_owned()),
+            ),
+            (
+                "tg://bg?color=abcdeg",
+                WallpaperSpecError::InvalidColor("abcdeg".to_owned()),
+            ),
+            (
+                "tg://bg?color=aaaaaa~bbbbbb~cccccc~dddddd~eeeeee",
+                WallpaperSpecError::InvalidColorCount(5),
+            ),
+            (
+                "t.me/bg/abc?intensity=101",
+                WallpaperSpecError::InvalidIntensity("101".to_owned()),
+            ),
+            (
+                "t.me/bg/abc?rotation=30",
+                WallpaperSpecError::InvalidRotation("30".to_owned()),
+            ),
+            (
+                "t.me/bg/a.b",
+                WallpaperSpecError::InvalidSlug("a.b".to_owned()),
+            ),
+        ];
+
+        for (input, error) in cases {
+            assert_eq!(input.parse::<WallpaperSpec>(), Err(error), "{input}");
+        }
+    }
+
+    #[test]
+    fn to_link_rejects_invalid_state() {
+        assert_eq!(
+            WallpaperSpec::default().to_link(),
+            Err(WallpaperSpecError::Empty)
+        );
+
+        let spec = WallpaperSpec {
+            colors: vec![0; 5],
+            ..Default::default()
+        };
+        assert_eq!(
+            spec.to_link(),
+            Err(WallpaperSpecError::InvalidColorCount(5))
+        );
+    }
+
+    #[test]
+    fn from_settings() {
+        let settings = WallpaperSettings {
+            background_color: Some(0xaaaaaa),
+            second_background_color: Some(0xbbbbbb),
+            intensity: Some(-40),
+            rotation: Some(-90),
+            motion: true,
+            ..Default::default()
+        };
+
+        let spec = WallpaperSpec::from_settings(Some("slug"), &settings).unwrap();
+
+        assert_eq!(spec.colors, [0xaaaaaa, 0xbbbbbb]);
+        assert_eq!(spec.rotation, 270);
+        assert_eq!(
+            spec.to_link().unwrap(),
+            "https://t.me/bg/slug?bg_color=aaaaaa-bbbbbb&intensity=-40&rotation=270&mode=motion"
+        );
+
+        assert_eq!(
+            WallpaperSpec::from_settings(None, &WallpaperSettings::default()),
+            Err(WallpaperSpecError::Empty)
+        );
+    }
+}
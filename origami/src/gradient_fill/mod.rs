@@ -0,0 +1,320 @@
+use std::cell::RefCell;
+
+use adw::prelude::*;
+use adw::subclass::prelude::*;
+use glib::clone;
+use gtk::glib;
+use gtk::graphene;
+
+use crate::gradient_bg;
+use crate::ColorSpace;
+use crate::GradientBg;
+use crate::GradientRenderer;
+
+mod imp {
+    use super::*;
+
+    #[derive(Default, glib::Properties)]
+    #[properties(wrapper_type = super::GradientFill)]
+    pub struct GradientFill {
+        #[property(name = "colors", get = Self::colors, set = Self::set_colors, type = Vec<String>)]
+        #[property(
+            name = "phase",
+            get = Self::phase,
+            set = Self::set_phase,
+            maximum = gradient_bg::PHASE_COUNT - 1,
+            type = u32
+        )]
+        #[property(
+            name = "progress",
+            get = Self::progress,
+            set = Self::set_progress,
+            minimum = 0.0,
+            maximum = 1.0,
+            type = f32
+        )]
+        #[property(name = "rotation", get = Self::rotation, set = Self::set_rotation, type = i32)]
+        #[property(
+            name = "color-space",
+            get = Self::color_space,
+            set = Self::set_color_space,
+            type = ColorSpace,
+            builder(ColorSpace::Srgb)
+        )]
+        pub(super) gradient: gradient_bg::FollowedGradient,
+
+        #[property(get, set = Self::set_viewport, nullable)]
+        pub(super) viewport: glib::WeakRef<gtk::Widget>,
+        /// Adjustments of the scrolled windows around the widget, scrolling moves the gradient
+        pub(super) adjustments: RefCell<Vec<(gtk::Adjustment, glib::SignalHandlerId)>>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for GradientFill {
+        const NAME: &'static str = "OriGradientFill";
+        type Type = super::GradientFill;
+        type ParentType = adw::Bin;
+    }
+
+    impl ObjectImpl for GradientFill {
+        fn properties() -> &'static [glib::ParamSpec] {
+            Self::derived_properties()
+        }
+
+        fn property(&self, id: usize, pspec: &glib::ParamSpec) -> glib::Value {
+            self.derived_property(id, pspec)
+        }
+
+        fn set_property(&self, id: usize, value: &glib::Value, pspec: &glib::ParamSpec) {
+            self.derived_set_property(id, value, pspec)
+        }
+
+        fn dispose(&self) {
+            self.disconnect_adjustments();
+            self.gradient.unfollow();
+        }
+    }
+
+    impl WidgetImpl for GradientFill {
+        fn map(&self) {
+            self.parent_map();
+            self.connect_adjustments();
+        }
+
+        fn unmap(&self) {
+            self.disconnect_adjustments();
+            self.parent_unmap();
+        }
+
+        fn snapshot(&self, snapshot: &gtk::Snapshot) {
+            let widget = self.obj();
+
+            let width = widget.width() as f32;
+            let height = widget.height() as f32;
+
+            if let Some(viewport_bounds) = self
+                .effective_viewport()
+                .and_then(|viewport| viewport.compute_bounds(&*widget))
+                .filter(|_| width > 0.0 && height > 0.0)
+            {
+                snapshot.push_clip(&graphene::Rect::new(0.0, 0.0, width, height));
+                gradient_bg::snapshot_gradient(
+                    snapshot,
+                    &viewport_bounds,
+                    &self.gradient.renderer.borrow(),
+                    &self.gradient.texture,
+                    None,
+                    clone!(
+                        #[weak]
+                        widget,
+                        move || widget.queue_draw()
+                    ),
+                );
+                snapshot.pop();
+            }
+
+            if let Some(child) = widget.child() {
+                widget.snapshot_child(&child, snapshot);
+            }
+        }
+    }
+
+    impl BinImpl for GradientFill {}
+
+    impl GradientFill {
+        fn colors(&self) -> Vec<String> {
+            self.gradient.colors()
+        }
+
+        fn set_colors(&self, colors: Vec<String>) {
+            if self.gradient.set_colors(&colors) {
+                self.colors_changed();
+            }
+        }
+
+        pub(super) fn set_theme_colors(&self, colors: &[i32]) {
+            if self.gradient.set_theme_colors(colors) {
+                self.colors_changed();
+            }
+        }
+
+        pub(super) fn set_gradient_from(&self, source: &GradientRenderer) {
+            if self.gradient.set_gradient_from(source) {
+                self.colors_changed();
+            }
+        }
+
+        fn colors_changed(&self) {
+            self.obj().queue_draw();
+            self.obj().notify_colors();
+        }
+
+        fn phase(&self) -> u32 {
+            self.gradient.phase()
+        }
+
+        fn set_phase(&self, phase: u32) {
+            self.gradient.set_phase(phase);
+            self.obj().queue_draw();
+        }
+
+        fn progress(&self) -> f32 {
+            self.gradient.progress()
+        }
+
+        fn set_progress(&self, progress: f32) {
+            self.gradient.set_progress(progress);
+            self.obj().queue_draw();
+        }
+
+        fn rotation(&self) -> i32 {
+            self.gradient.rotation()
+        }
+
+        fn set_rotation(&self, rotation: i32) {
+            self.gradient.set_rotation(rotation);
+            self.obj().queue_draw();
+        }
+
+        fn color_space(&self) -> ColorSpace {
+            self.gradient.color_space()
+        }
+
+        fn set_color_space(&self, color_space: ColorSpace) {
+            self.gradient.set_color_space(color_space);
+            self.obj().queue_draw();
+        }
+
+        fn set_viewport(&self, viewport: Option<&gtk::Widget>) {
+            self.viewport.set(viewport);
+
+            if self.obj().is_mapped() {
+                self.disconnect_adjustments();
+                self.connect_adjustments();
+            }
+
+            self.obj().queue_draw();
+        }
+
+        /// Returns the viewport or the closest scrolled window or the window
+        fn effective_viewport(&self) -> Option<gtk::Widget> {
+            let widget = self.obj();
+
+            self.viewport
+                .upgrade()
+                .or_else(|| widget.ancestor(gtk::ScrolledWindow::static_type()))
+                .or_else(|| widget.root().and_upcast())
+        }
+
+        /// Redraws the widget when it's scrolled inside the viewport
+        ///
+        /// Scrolling only moves render nodes of the children,
+        /// so the widget isn't redrawn on its own.
+        fn connect_adjustments(&self) {
+            let widget = self.obj();
+            let viewport = self.effective_viewport();
+
+            let mut adjustments = self.adjustments.borrow_mut();
+            let mut ancestor = widget.parent();
+
+            while let Some(current) = ancestor {
+                if Some(&current) == viewport.as_ref() {
+                    break;
+                }
+
+                if let Some(scrolled_window) = current.downcast_ref::<gtk::ScrolledWindow>() {
+                    for adjustment in [scrolled_window.hadjustment(), scrolled_window.vadjustment()]
+                    {
+                        let handler = adjustment.connect_value_changed(clone!(
+                            #[weak]
+                            widget,
+                            move |_| widget.queue_draw()
+                        ));
+                        adjustments.push((adjustment, handler));
+                    }
+                }
+
+                ancestor = current.parent();
+            }
+        }
+
+        fn disconnect_adjustments(&self) {
+            for (adjustment, handler) in self.adjustments.take() {
+                adjustment.disconnect(handler);
+            }
+        }
+    }
+}
+
+glib::wrapper! {
+    #[doc(alias = "OriGradientFill")]
+    /// Fill for message bubbles that shows its own part of a viewport-wide gradient
+    ///
+    /// Like outgoing messages in Telegram gradient themes, the gradient is stretched
+    /// over the viewport and every widget shows only the part under its bounds,
+    /// so the colors shift while scrolling.
+    ///
+    /// The widget is filled with a rectangle,
+    /// rounded corners can be made with `border-radius` and `overflow: hidden` in CSS.
+    ///
+    /// # Properties
+    ///
+    /// * colors, phase, progress, rotation, color-space.
+    /// Same as in [GradientBg], colors are parsed with [gdk::RGBA::parse](gtk::gdk::RGBA::parse).
+    /// [follow_gradient_bg](GradientFill::follow_gradient_bg) binds them to a wallpaper.
+    ///
+    /// * viewport: [Option]<[gtk::Widget]>.
+    /// Widget whose bounds the gradient is stretched over.
+    /// By default it's the closest [gtk::ScrolledWindow] or the window.
+    ///
+    /// # Bluerpint example
+    /// ```blp
+    /// $OriGradientFill {
+    ///     colors: ["#fff4bf", "#fcd8a8", "#f2c8c0", "#f7d6e3"];
+    ///     styles ["bubble"]
+    ///
+    ///     Label {
+    ///         label: "Hello";
+    ///     }
+    /// }
+    /// ```
+    pub struct GradientFill(ObjectSubclass<imp::GradientFill>)
+        @extends gtk::Widget, adw::Bin;
+}
+
+impl GradientFill {
+    pub fn new() -> Self {
+        glib::Object::new()
+    }
+
+    /// Takes int colors as from theme returned by tdlib, same as [GradientBg::set_theme_colors]
+    pub fn set_theme_colors(&self, colors: &[i32]) {
+        self.imp().set_theme_colors(colors);
+    }
+
+    /// Samples the gradient of the wallpaper
+    ///
+    /// Binds colors and animation of the fill to the [GradientBg]
+    /// and uses it as the viewport, so the fill matches the wallpaper under it.
+    /// Custom points are copied together with the colors.
+    /// Bindings to the previously followed widget are removed.
+    pub fn follow_gradient_bg(&self, gradient_bg: &GradientBg) {
+        self.imp().gradient.follow(
+            self,
+            gradient_bg,
+            clone!(
+                #[weak(rename_to = fill)]
+                self,
+                move |renderer| fill.imp().set_gradient_from(renderer)
+            ),
+        );
+
+        self.set_viewport(Some(gradient_bg.upcast_ref::<gtk::Widget>()));
+    }
+}
+
+impl Default for GradientFill {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -1,23 +1,40 @@
 //! [Paper Plane](https://github.com/paper-plane-developers/paper-plane) related set of gtk widgets that can be usable outside of it.
 
 mod gradient_bg;
+mod gradient_fill;
 mod loading_indicator;
 mod shimmer_effect;
 mod spoiler_overlay;
+mod text_spoiler;
 
 use gtk::prelude::StaticType;
 
+pub use gradient_bg::contrast_ratio;
+pub use gradient_bg::extract_theme_colors;
+pub use gradient_bg::ColorSpace;
 pub use gradient_bg::GradientBg;
+pub use gradient_bg::GradientPaintable;
+pub use gradient_bg::GradientPreset;
+pub use gradient_bg::GradientRenderer;
+pub use gradient_bg::ServiceColors;
+pub use gradient_bg::WallpaperSettings;
+pub use gradient_bg::WallpaperSpec;
+pub use gradient_bg::WallpaperSpecError;
+pub use gradient_fill::GradientFill;
 pub use loading_indicator::LoadingIndicator;
 pub use shimmer_effect::ShimmerEffect;
 pub use spoiler_overlay::SpoilerOverlay;
+pub use text_spoiler::TextSpoiler;
 
 /// Registers all library types.
 ///
 /// Expected to be called in the main function
 pub fn init() {
     GradientBg::static_type();
+    GradientFill::static_type();
+    GradientPaintable::static_type();
     LoadingIndicator::static_type();
     ShimmerEffect::static_type();
     SpoilerOverlay::static_type();
+    TextSpoiler::static_type();
 }
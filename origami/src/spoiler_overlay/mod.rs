@@ -1,7 +1,6 @@
 use std::cell::Cell;
 use std::cell::OnceCell;
 use std::cell::RefCell;
-use std::sync::OnceLock;
 
 use adw::prelude::*;
 use adw::subclass::prelude::*;
@@ -12,13 +11,11 @@ use gtk::glib;
 use gtk::graphene;
 use gtk::gsk;
 
-fn particle_texture() -> &'static gdk::Texture {
-    static PARTICLE_TEXTURE: OnceLock<gdk::Texture> = OnceLock::new();
-    PARTICLE_TEXTURE.get_or_init(|| {
-        let bytes = glib::Bytes::from_static(include_bytes!("turbulence_2x.png"));
-        gdk::Texture::from_bytes(&bytes).unwrap()
-    })
-}
+mod particles;
+
+pub(crate) use particles::snapshot_particles;
+pub(crate) use particles::widget_scale;
+pub(crate) use particles::ParticleParams;
 
 const BLUR_SHADER: &[u8] = include_bytes!("blur_shader.frag");
 
@@ -40,6 +37,51 @@ mod imp {
 
         #[property(get, set = Self::set_hidden)]
         pub(super) hidden: Cell<bool>,
+
+        #[property(
+            name = "particle-density",
+            get = Self::particle_density,
+            set = Self::set_particle_density,
+            type = f32,
+            minimum = 0.0,
+            default = particles::DEFAULT_DENSITY
+        )]
+        #[property(
+            name = "particle-size",
+            get = Self::particle_size,
+            set = Self::set_particle_size,
+            type = f32,
+            minimum = 0.0,
+            default = particles::DEFAULT_SIZE
+        )]
+        #[property(
+            name = "particle-speed",
+            get = Self::particle_speed,
+            set = Self::set_particle_speed,
+            type = f32,
+            minimum = 0.0,
+            default = particles::DEFAULT_SPEED
+        )]
+        #[property(
+            name = "particle-lifetime",
+            get = Self::particle_lifetime,
+            set = Self::set_particle_lifetime,
+            type = u32,
+            default = particles::DEFAULT_LIFETIME
+        )]
+        #[property(
+            name = "particle-color",
+            get = Self::particle_color,
+            set = Self::set_particle_color,
+            type = gdk::RGBA
+        )]
+        #[property(
+            name = "particle-seed",
+            get = Self::particle_seed,
+            set = Self::set_particle_seed,
+            type = u32
+        )]
+        pub(super) particles: RefCell<ParticleParams>,
     }
 
     #[glib::object_subclass]
@@ -143,61 +185,22 @@ mod imp {
                 self.click_point.get()
             };
 
-            let center = &graphene::Point::new(x, y);
-
-            let max_corner_length = [
-                bounds.top_left(),
-                bounds.top_right(),
-                bounds.bottom_left(),
-                bounds.bottom_right(),
-            ]
-            .into_iter()
-            .map(|v| (v.distance(center)).0)
-            .max_by(|this, other| this.partial_cmp(other).unwrap())
-            .unwrap();
-
-            let progress = self.reveal_progress.get();
-            let radius = max_corner_length * progress;
-
-            if radius > 0.0 {
-                snapshot.push_mask(gsk::MaskMode::InvertedAlpha);
-
-                snapshot.append_radial_gradient(
-                    &bounds,
-                    &graphene::Point::new(x, y),
-                    radius,
-                    radius,
-                    0.0,
-                    1.0,
-                    &[
-                        gsk::ColorStop::new(0.0, gdk::RGBA::BLACK),
-                        gsk::ColorStop::new(progress, gdk::RGBA::BLACK),
-                        gsk::ColorStop::new((progress + 0.5).min(1.0), gdk::RGBA::TRANSPARENT),
-                        gsk::ColorStop::new(1.0, gdk::RGBA::TRANSPARENT),
-                    ],
-                );
-                snapshot.pop();
-            }
+            let center = graphene::Point::new(x, y);
+
+            let masked = push_reveal_mask(snapshot, &bounds, &center, self.reveal_progress.get());
 
             self.render_blur_texture(snapshot, &bounds);
 
             let time = widget.time() - self.start_time.get();
-            let time = time as f32 / 50000.0;
-
-            let speed_modifiers = &[
-                (0.468, 0.287),
-                (0.305, 0.1967),
-                (0.316, 0.3239),
-                (-0.0239, 0.7745),
-                (-0.0736, 0.2023),
-                (0.5138, -0.15),
-                (0.5603, -0.8172),
-                (-0.8098, -0.8822),
-            ];
-
-            self.render_particle_layers(snapshot, &bounds, time, speed_modifiers);
-
-            if radius > 0.0 {
+            snapshot_particles(
+                snapshot,
+                &bounds,
+                time,
+                &self.particles.borrow(),
+                widget_scale(&*widget),
+            );
+
+            if masked {
                 snapshot.pop();
             }
         }
@@ -214,6 +217,60 @@ mod imp {
             self.hidden.set(hidden);
         }
 
+        fn particle_density(&self) -> f32 {
+            self.particles.borrow().density
+        }
+
+        fn set_particle_density(&self, density: f32) {
+            self.particles.borrow_mut().density = density.max(0.0);
+            self.obj().queue_draw();
+        }
+
+        fn particle_size(&self) -> f32 {
+            self.particles.borrow().size
+        }
+
+        fn set_particle_size(&self, size: f32) {
+            self.particles.borrow_mut().size = size.max(0.0);
+            self.obj().queue_draw();
+        }
+
+        fn particle_speed(&self) -> f32 {
+            self.particles.borrow().speed
+        }
+
+        fn set_particle_speed(&self, speed: f32) {
+            self.particles.borrow_mut().speed = speed.max(0.0);
+            self.obj().queue_draw();
+        }
+
+        fn particle_lifetime(&self) -> u32 {
+            self.particles.borrow().lifetime
+        }
+
+        fn set_particle_lifetime(&self, lifetime: u32) {
+            self.particles.borrow_mut().lifetime = lifetime;
+            self.obj().queue_draw();
+        }
+
+        fn particle_color(&self) -> gdk::RGBA {
+            self.particles.borrow().color
+        }
+
+        fn set_particle_color(&self, color: gdk::RGBA) {
+            self.particles.borrow_mut().color = color;
+            self.obj().queue_draw();
+        }
+
+        fn particle_seed(&self) -> u32 {
+            self.particles.borrow().seed
+        }
+
+        fn set_particle_seed(&self, seed: u32) {
+            self.particles.borrow_mut().seed = seed;
+            self.obj().queue_draw();
+        }
+
         fn ensure_shader(&self) {
             let widget = self.obj();
             if self.shader.borrow().is_none() {
@@ -272,35 +329,6 @@ mod imp {
                 snapshot.append_color(&gdk::RGBA::new(0.3, 0.3, 0.3, 1.0), bounds);
             }
         }
-
-        fn render_particle_layers(
-            &self,
-            snapshot: &gtk::Snapshot,
-            bounds: &graphene::Rect,
-            time: f32,
-            speed_modifiers: &[(f32, f32)],
-        ) {
-            let texture = particle_texture();
-
-            let texture_bounds = {
-                // Texture have 2x size, so we divide it to scale correctly;
-                let width = texture.width() as f32 / 2.0;
-                let height = texture.height() as f32 / 2.0;
-
-                graphene::Rect::new(0.0, 0.0, width, height)
-            };
-
-            for (x, y) in speed_modifiers {
-                let x = x * time;
-                let y = y * time;
-
-                snapshot.push_repeat(bounds, None);
-                snapshot.translate(&graphene::Point::new(x, y));
-                snapshot.append_texture(texture, &texture_bounds);
-                snapshot.translate(&graphene::Point::new(-x, -y));
-                snapshot.pop();
-            }
-        }
     }
 }
 
@@ -321,6 +349,25 @@ glib::wrapper! {
     /// Controls how overlay appears and disappears,
     /// It was made as a property to be visible in the Inspector
     ///
+    /// * particle-density: [f32].
+    /// Number of particles on 100x100 pixels, 80 by default.
+    /// The number of particles grows with the area of the overlay.
+    ///
+    /// * particle-size: [f32].
+    /// Size of particles in pixels, 1.5 by default. Particles are aligned to device pixels.
+    ///
+    /// * particle-speed: [f32].
+    /// Average speed of particles in pixels per second, 12 by default.
+    ///
+    /// * particle-lifetime: [u32].
+    /// Average lifetime of a particle in milliseconds, 2000 by default.
+    ///
+    /// * particle-color: [gdk::RGBA].
+    /// White by default, use a dark color over light media.
+    ///
+    /// * particle-seed: [u32].
+    /// Particles move in the same way for the same seed and time.
+    ///
     /// # Bluerpint example
     /// ```blp
     /// $OriSpoilerOverlay {
@@ -353,3 +400,50 @@ impl SpoilerOverlay {
             .unwrap_or_default()
     }
 }
+
+/// Starts the mask that hides the content outside of the circle growing from `center`
+///
+/// Returns `true` if the mask is pushed, the caller should pop it after drawing the content.
+pub(crate) fn push_reveal_mask(
+    snapshot: &gtk::Snapshot,
+    bounds: &graphene::Rect,
+    center: &graphene::Point,
+    progress: f32,
+) -> bool {
+    let max_corner_length = [
+        bounds.top_left(),
+        bounds.top_right(),
+        bounds.bottom_left(),
+        bounds.bottom_right(),
+    ]
+    .into_iter()
+    .map(|v| (v.distance(center)).0)
+    .max_by(|this, other| this.partial_cmp(other).unwrap())
+    .unwrap();
+
+    let radius = max_corner_length * progress;
+
+    if radius <= 0.0 {
+        return false;
+    }
+
+    snapshot.push_mask(gsk::MaskMode::InvertedAlpha);
+
+    snapshot.append_radial_gradient(
+        bounds,
+        center,
+        radius,
+        radius,
+        0.0,
+        1.0,
+        &[
+            gsk::ColorStop::new(0.0, gdk::RGBA::BLACK),
+            gsk::ColorStop::new(progress, gdk::RGBA::BLACK),
+            gsk::ColorStop::new((progress + 0.5).min(1.0), gdk::RGBA::TRANSPARENT),
+            gsk::ColorStop::new(1.0, gdk::RGBA::TRANSPARENT),
+        ],
+    );
+    snapshot.pop();
+
+    true
+}
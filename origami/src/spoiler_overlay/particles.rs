@@ -0,0 +1,333 @@
+use std::cell::RefCell;
+use std::f32::consts::PI;
+
+use gtk::gdk;
+use gtk::graphene;
+use gtk::gsk;
+use gtk::prelude::*;
+
+/// Number of particles on 100x100 pixels
+pub(crate) const DEFAULT_DENSITY: f32 = 80.0;
+/// Particle size in pixels
+pub(crate) const DEFAULT_SIZE: f32 = 1.5;
+/// Particle speed in pixels per second
+pub(crate) const DEFAULT_SPEED: f32 = 12.0;
+/// Particle lifetime in milliseconds
+pub(crate) const DEFAULT_LIFETIME: u32 = 2000;
+
+/// Limit for huge widgets, particles are barely visible there anyway
+const MAX_PARTICLES: u32 = 20000;
+
+/// Number of groups of particles that move together
+const GROUP_COUNT: u32 = 16;
+
+/// Maximum number of particle layers shared between all widgets of the thread
+const LAYERS_LIMIT: usize = 512;
+
+/// Parameters of the particle effect
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct ParticleParams {
+    pub density: f32,
+    pub size: f32,
+    pub speed: f32,
+    pub lifetime: u32,
+    pub color: gdk::RGBA,
+    /// Particles with the same seed move in the same way
+    pub seed: u32,
+}
+
+impl Default for ParticleParams {
+    fn default() -> Self {
+        Self {
+            density: DEFAULT_DENSITY,
+            size: DEFAULT_SIZE,
+            speed: DEFAULT_SPEED,
+            lifetime: DEFAULT_LIFETIME,
+            color: gdk::RGBA::WHITE,
+            seed: 0,
+        }
+    }
+}
+
+/// Returns a pseudo random number from 0 to 1 for the particle
+///
+/// It's a hash of the arguments, so a particle looks the same in every frame of its life.
+fn random(seed: u32, index: u32, cycle: u32, channel: u32) -> f32 {
+    let mut hash = seed
+        ^ index.wrapping_mul(0x9e37_79b9)
+        ^ cycle.wrapping_mul(0x85eb_ca6b)
+        ^ channel.wrapping_mul(0xc2b2_ae35);
+
+    // Finalizer of MurmurHash3
+    hash ^= hash >> 16;
+    hash = hash.wrapping_mul(0x85eb_ca6b);
+    hash ^= hash >> 13;
+    hash = hash.wrapping_mul(0xc2b2_ae35);
+    hash ^= hash >> 16;
+
+    (hash >> 8) as f32 / (1 << 24) as f32
+}
+
+/// Returns the scale of the surface, it's fractional unlike [WidgetExt::scale_factor]
+pub(crate) fn widget_scale(widget: &impl IsA<gtk::Widget>) -> f32 {
+    widget
+        .native()
+        .and_then(|native| native.surface())
+        .map(|surface| surface.scale() as f32)
+        .unwrap_or_else(|| widget.scale_factor() as f32)
+}
+
+/// State of a group of particles at some moment
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct GroupFrame {
+    /// Particles appear at new points in every cycle
+    cycle: u32,
+    /// Distance the particles have flown since the start of the cycle
+    offset: (f32, f32),
+    /// Particles fade in and out during the cycle
+    opacity: f32,
+}
+
+/// Returns the state of the group, `time` is in seconds
+///
+/// Groups live different time and start at different moments,
+/// so particles don't blink all together.
+fn group_frame(group: u32, time: f32, params: &ParticleParams) -> GroupFrame {
+    let random = |channel| random(params.seed, group, 0, channel);
+
+    let lifetime = params.lifetime as f32 / 1000.0 * (0.75 + 0.5 * random(0));
+    let time = time + lifetime * random(1);
+
+    let cycle = (time / lifetime) as u32;
+    let age = time / lifetime - cycle as f32;
+
+    let random = |channel| self::random(params.seed, group, cycle, channel);
+
+    let angle = random(2) * 2.0 * PI;
+    let distance = params.speed * (0.5 + random(3)) * age * lifetime;
+
+    GroupFrame {
+        cycle,
+        offset: (angle.cos() * distance, angle.sin() * distance),
+        opacity: (age * PI).sin(),
+    }
+}
+
+/// Returns start points of the particles of the group in the cycle
+///
+/// Particles are spread between groups by index, points are relative to the bounds.
+fn group_points(
+    group: u32,
+    cycle: u32,
+    count: u32,
+    width: f32,
+    height: f32,
+    params: &ParticleParams,
+) -> impl Iterator<Item = (f32, f32)> + '_ {
+    (group..count)
+        .step_by(GROUP_COUNT as usize)
+        .map(move |index| {
+            // Channels 0 to 3 are used by groups, so particle values are independent of them
+            let x = random(params.seed, index, cycle, 4) * width;
+            let y = random(params.seed, index, cycle, 5) * height;
+            (x, y)
+        })
+}
+
+/// Layer with particles of one group in one cycle
+struct CachedLayer {
+    params: ParticleParams,
+    width: f32,
+    height: f32,
+    scale: f32,
+    group: u32,
+    cycle: u32,
+    node: Option<gsk::RenderNode>,
+}
+
+thread_local! {
+    static LAYERS: RefCell<Vec<CachedLayer>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Returns the node with particles of the group at their start points
+///
+/// Particles of a group only move and fade together during a cycle,
+/// so the node is built once per cycle and shared by every frame of it.
+fn group_layer(
+    group: u32,
+    cycle: u32,
+    count: u32,
+    bounds: &graphene::Rect,
+    params: &ParticleParams,
+    scale: f32,
+) -> Option<gsk::RenderNode> {
+    let (width, height) = (bounds.width(), bounds.height());
+
+    LAYERS.with_borrow_mut(|layers| {
+        let index = layers.iter().position(|layer| {
+            layer.group == group
+                && layer.cycle == cycle
+                && layer.width == width
+                && layer.height == height
+                && layer.scale == scale
+                && layer.params == *params
+        });
+
+        // Move to the end, so the least recently used layer is always first
+        if let Some(index) = index {
+            let layer = layers.remove(index);
+            let node = layer.node.clone();
+            layers.push(layer);
+            return node;
+        }
+
+        let size = (params.size * scale).round().max(1.0) / scale;
+        let snap = |value: f32| (value * scale).round() / scale;
+
+        let snapshot = gtk::Snapshot::new();
+        for (x, y) in group_points(group, cycle, count, width, height, params) {
+            snapshot.append_color(
+                &params.color,
+                &graphene::Rect::new(snap(x - size / 2.0), snap(y - size / 2.0), size, size),
+            );
+        }
+        let node = snapshot.to_node();
+
+        if layers.len() >= LAYERS_LIMIT {
+            layers.remove(0);
+        }
+
+        layers.push(CachedLayer {
+            params: *params,
+            width,
+            height,
+            scale,
+            group,
+            cycle,
+            node: node.clone(),
+        });
+
+        node
+    })
+}
+
+/// Draws moving particles over the bounds
+///
+/// `time` is in microseconds since the start of the effect.
+/// Every particle appears at a random point, flies in a random direction and fades out,
+/// then it appears again at another point. The number of particles depends on the area,
+/// particles are aligned to device pixels using `scale`.
+///
+/// Particles are split into groups that share lifetime, direction and speed,
+/// so every frame only moves and fades a cached layer of each group.
+pub(crate) fn snapshot_particles(
+    snapshot: &gtk::Snapshot,
+    bounds: &graphene::Rect,
+    time: i64,
+    params: &ParticleParams,
+    scale: f32,
+) {
+    let area = bounds.width() * bounds.height();
+    let count = ((area / 10000.0 * params.density) as u32).min(MAX_PARTICLES);
+
+    if count == 0 || params.lifetime == 0 || params.color.alpha() == 0.0 {
+        return;
+    }
+
+    let time = time.max(0) as f32 / 1_000_000.0;
+
+    let scale = scale.max(1.0);
+    let snap = |value: f32| (value * scale).round() / scale;
+
+    snapshot.push_clip(bounds);
+
+    for group in 0..GROUP_COUNT.min(count) {
+        let frame = group_frame(group, time, params);
+
+        let Some(layer) = group_layer(group, frame.cycle, count, bounds, params, scale) else {
+            continue;
+        };
+
+        let (offset_x, offset_y) = frame.offset;
+
+        snapshot.save();
+        snapshot.translate(&graphene::Point::new(
+            snap(bounds.x() + offset_x),
+            snap(bounds.y() + offset_y),
+        ));
+        snapshot.push_opacity(frame.opacity as f64);
+        snapshot.append_node(&layer);
+        snapshot.pop();
+        snapshot.restore();
+    }
+
+    snapshot.pop();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns points and alpha of every particle like they are drawn
+    fn particles(
+        width: f32,
+        height: f32,
+        time: f32,
+        params: &ParticleParams,
+    ) -> Vec<((f32, f32), f32)> {
+        let count = (width * height / 10000.0 * params.density) as u32;
+
+        (0..GROUP_COUNT.min(count))
+            .flat_map(|group| {
+                let frame = group_frame(group, time, params);
+                let (offset_x, offset_y) = frame.offset;
+                let alpha = params.color.alpha() * frame.opacity;
+
+                group_points(group, frame.cycle, count, width, height, params)
+                    .map(move |(x, y)| ((x + offset_x, y + offset_y), alpha))
+                    .collect::<Vec<_>>()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn stable_for_seed_and_time() {
+        let params = ParticleParams {
+            seed: 7,
+            color: gdk::RGBA::new(1.0, 1.0, 1.0, 0.5),
+            ..Default::default()
+        };
+
+        let particles = particles(200.0, 100.0, 1.25, &params);
+        assert_eq!(particles.len(), 160);
+        assert_eq!(particles, self::particles(200.0, 100.0, 1.25, &params));
+
+        // Particles fly at most 1.5 of the speed for 1.25 of the lifetime
+        let max_distance = params.speed * 1.5 * params.lifetime as f32 / 1000.0 * 1.25;
+        for &((x, y), alpha) in &particles {
+            assert!((-max_distance..=200.0 + max_distance).contains(&x));
+            assert!((-max_distance..=100.0 + max_distance).contains(&y));
+            assert!((0.0..=0.5).contains(&alpha));
+        }
+
+        let other_seed = ParticleParams { seed: 8, ..params };
+        assert_ne!(particles, self::particles(200.0, 100.0, 1.25, &other_seed));
+        assert_ne!(particles, self::particles(200.0, 100.0, 1.5, &params));
+    }
+
+    #[test]
+    fn groups_are_continuous_within_cycle() {
+        let params = ParticleParams::default();
+
+        for group in 0..GROUP_COUNT {
+            let frame = group_frame(group, 3.0, &params);
+            let next = group_frame(group, 3.001, &params);
+
+            if frame.cycle == next.cycle {
+                assert!((frame.offset.0 - next.offset.0).abs() < 0.1);
+                assert!((frame.offset.1 - next.offset.1).abs() < 0.1);
+                assert!((frame.opacity - next.opacity).abs() < 0.01);
+            }
+        }
+    }
+}
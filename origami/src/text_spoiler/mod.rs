@@ -0,0 +1,745 @@
+use std::cell::Cell;
+use std::cell::RefCell;
+use std::ops::Range;
+
+use adw::prelude::*;
+use adw::subclass::prelude::*;
+use glib::clone;
+use gtk::gdk;
+use gtk::glib;
+use gtk::graphene;
+use gtk::gsk;
+use gtk::pango;
+
+use crate::spoiler_overlay;
+
+/// Duration of the reveal animation in milliseconds, same as in [SpoilerOverlay](crate::SpoilerOverlay)
+const REVEAL_DURATION: u32 = 1000;
+
+/// Units of the spoiler ranges
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub(super) enum RangeUnits {
+    #[default]
+    Bytes,
+    /// Like offsets of text entities in tdlib
+    Utf16,
+}
+
+pub(super) struct Spoiler {
+    range: Range<usize>,
+    /// From 0 when the text is hidden to 1 when it's revealed
+    reveal_progress: Cell<f32>,
+    click_point: Cell<(f32, f32)>,
+    animation: RefCell<Option<adw::TimedAnimation>>,
+}
+
+/// Child that is concealed while some text is hidden
+enum ConcealedChild {
+    /// Label and whether it was selectable
+    Label(gtk::Label, bool),
+    /// Buffer of a text view and the handler that unselects hidden ranges
+    TextView(gtk::TextBuffer, glib::SignalHandlerId),
+}
+
+impl Spoiler {
+    fn new(range: Range<usize>) -> Self {
+        Self {
+            range,
+            reveal_progress: Cell::new(0.0),
+            click_point: Cell::new((0.0, 0.0)),
+            animation: Default::default(),
+        }
+    }
+
+    fn is_revealed(&self) -> bool {
+        self.reveal_progress.get() >= 1.0
+    }
+
+    /// Returns `true` if the spoiler is hidden and isn't being revealed
+    fn is_hidden(&self) -> bool {
+        self.reveal_progress.get() == 0.0 && self.animation.borrow().is_none()
+    }
+}
+
+mod imp {
+    use super::*;
+
+    #[derive(Default)]
+    pub struct TextSpoiler {
+        pub(super) spoilers: RefCell<Vec<Spoiler>>,
+        pub(super) units: Cell<RangeUnits>,
+        /// Layout set explicitly and its position in the widget
+        pub(super) layout: RefCell<Option<(pango::Layout, graphene::Point)>>,
+        pub(super) start_time: Cell<i64>,
+        pub(super) ticking: Cell<bool>,
+        pub(super) concealed_child: RefCell<Option<ConcealedChild>>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for TextSpoiler {
+        const NAME: &'static str = "OriTextSpoiler";
+        type Type = super::TextSpoiler;
+        type ParentType = adw::Bin;
+    }
+
+    impl ObjectImpl for TextSpoiler {
+        fn constructed(&self) {
+            self.parent_constructed();
+
+            let widget = self.obj();
+
+            // Labels and text views handle clicks too, so the click is taken before them
+            let controller = gtk::GestureClick::builder()
+                .button(1)
+                .propagation_phase(gtk::PropagationPhase::Capture)
+                .build();
+
+            controller.connect_pressed(clone!(
+                #[weak]
+                widget,
+                move |gesture, _button, x, y| {
+                    if widget.imp().reveal_at(x as f32, y as f32) {
+                        gesture.set_state(gtk::EventSequenceState::Claimed);
+                    }
+                }
+            ));
+
+            widget.add_controller(controller);
+
+            widget.connect_child_notify(|widget| widget.imp().update_concealed_child());
+        }
+    }
+
+    impl WidgetImpl for TextSpoiler {
+        fn map(&self) {
+            self.parent_map();
+            self.start_ticking();
+        }
+
+        fn snapshot(&self, snapshot: &gtk::Snapshot) {
+            let regions = self.hidden_regions();
+
+            if regions.is_empty() {
+                self.parent_snapshot(snapshot);
+                return;
+            }
+
+            // Hidden text isn't drawn at all, so it can't be read through the particles
+            snapshot.push_mask(gsk::MaskMode::InvertedAlpha);
+            for (rects, progress, center) in &regions {
+                Self::snapshot_hidden_region(snapshot, rects, *progress, center);
+            }
+            snapshot.pop();
+            self.parent_snapshot(snapshot);
+            snapshot.pop();
+
+            let widget = self.obj();
+            let time = widget.time() - self.start_time.get();
+            let scale = spoiler_overlay::widget_scale(&*widget);
+
+            for (rects, progress, center) in &regions {
+                snapshot.push_mask(gsk::MaskMode::Alpha);
+                Self::snapshot_hidden_region(snapshot, rects, *progress, center);
+                snapshot.pop();
+
+                for (index, rect) in rects.iter().enumerate() {
+                    // Particles have the color of the text, so they look fine on any background.
+                    // Every rect has its own seed, otherwise lines of the same width look the same
+                    let params = spoiler_overlay::ParticleParams {
+                        color: widget.color(),
+                        seed: index as u32,
+                        ..Default::default()
+                    };
+
+                    spoiler_overlay::snapshot_particles(snapshot, rect, time, &params, scale);
+                }
+
+                snapshot.pop();
+            }
+        }
+    }
+
+    impl BinImpl for TextSpoiler {}
+
+    impl TextSpoiler {
+        pub(super) fn set_ranges(&self, ranges: &[Range<usize>], units: RangeUnits) {
+            self.units.set(units);
+
+            let old_spoilers = self.spoilers.replace(
+                ranges
+                    .iter()
+                    .filter(|range| !range.is_empty())
+                    .map(|range| Spoiler::new(range.clone()))
+                    .collect(),
+            );
+
+            // Animations of old spoilers would change new ones with the same index
+            for spoiler in old_spoilers {
+                if let Some(animation) = spoiler.animation.take() {
+                    animation.pause();
+                }
+            }
+
+            self.start_ticking();
+            self.update_concealed_child();
+            self.obj().queue_draw();
+        }
+
+        /// Starts revealing the spoiler under the point
+        ///
+        /// Returns `false` if there is no hidden spoiler.
+        fn reveal_at(&self, x: f32, y: f32) -> bool {
+            let point = graphene::Point::new(x, y);
+
+            let index = self.spoilers.borrow().iter().position(|spoiler| {
+                spoiler.is_hidden()
+                    && self
+                        .range_rects(&spoiler.range)
+                        .iter()
+                        .any(|rect| rect.contains_point(&point))
+            });
+
+            match index {
+                Some(index) => {
+                    self.reveal(index, (x, y));
+                    true
+                }
+                None => false,
+            }
+        }
+
+        pub(super) fn reveal(&self, index: usize, click_point: (f32, f32)) {
+            let spoilers = self.spoilers.borrow();
+            let Some(spoiler) = spoilers.get(index) else {
+                return;
+            };
+
+            if !spoiler.is_hidden() {
+                return;
+            }
+
+            spoiler.click_point.set(click_point);
+
+            let widget = self.obj();
+
+            let target = adw::CallbackAnimationTarget::new(clone!(
+                #[weak]
+                widget,
+                move |progress| {
+                    if let Some(spoiler) = widget.imp().spoilers.borrow().get(index) {
+                        spoiler.reveal_progress.set(progress as f32);
+                    }
+                    widget.queue_draw();
+                }
+            ));
+
+            let animation = adw::TimedAnimation::builder()
+                .widget(&*widget)
+                .value_from(0.0)
+                .value_to(1.0)
+                .duration(REVEAL_DURATION)
+                .easing(adw::Easing::EaseInOutCubic)
+                .target(&target)
+                .build();
+
+            animation.connect_done(clone!(
+                #[weak]
+                widget,
+                move |_| widget.imp().update_concealed_child()
+            ));
+
+            spoiler.animation.replace(Some(animation.clone()));
+            drop(spoilers);
+
+            animation.play();
+        }
+
+        /// Redraws particles every frame while there are hidden spoilers
+        pub(super) fn start_ticking(&self) {
+            let widget = self.obj();
+
+            if self.ticking.get() || !widget.is_mapped() || !self.has_unrevealed() {
+                return;
+            }
+
+            self.ticking.set(true);
+            self.start_time.set(widget.time());
+
+            widget.add_tick_callback(|widget, _clock| {
+                widget.queue_draw();
+
+                let imp = widget.imp();
+                let ticking = widget.is_mapped() && imp.has_unrevealed();
+                imp.ticking.set(ticking);
+
+                glib::ControlFlow::from(ticking)
+            });
+        }
+
+        fn has_unrevealed(&self) -> bool {
+            self.spoilers
+                .borrow()
+                .iter()
+                .any(|spoiler| !spoiler.is_revealed())
+        }
+
+        /// Hidden text can't be selected or copied
+        ///
+        /// A label child is made non-selectable and hidden ranges are removed from its
+        /// accessible label, both are restored when everything is revealed.
+        /// Selections of a text view child are cleared when they include hidden ranges.
+        pub(super) fn update_concealed_child(&self) {
+            match self.concealed_child.take() {
+                Some(ConcealedChild::Label(label, selectable)) => {
+                    label.set_selectable(selectable);
+                    label.reset_property(gtk::AccessibleProperty::Label);
+                }
+                Some(ConcealedChild::TextView(buffer, handler)) => buffer.disconnect(handler),
+                None => (),
+            }
+
+            let widget = self.obj();
+
+            let Some(child) = widget.child().filter(|_| self.has_unrevealed()) else {
+                return;
+            };
+
+            if let Some(label) = child.downcast_ref::<gtk::Label>() {
+                self.concealed_child.replace(Some(ConcealedChild::Label(
+                    label.clone(),
+                    label.is_selectable(),
+                )));
+
+                label.set_selectable(false);
+
+                let text = label.layout().text();
+                let text = concealed_text(&text, &self.hidden_ranges(&text));
+                label.update_property(&[gtk::accessible::Property::Label(&text)]);
+            } else if let Some(text_view) = child.downcast_ref::<gtk::TextView>() {
+                let buffer = text_view.buffer();
+
+                let handler = buffer.connect_mark_set(clone!(
+                    #[weak]
+                    widget,
+                    move |buffer, _, mark| {
+                        if *mark == buffer.get_insert() || *mark == buffer.selection_bound() {
+                            widget.imp().unselect_hidden(buffer);
+                        }
+                    }
+                ));
+                self.unselect_hidden(&buffer);
+
+                self.concealed_child
+                    .replace(Some(ConcealedChild::TextView(buffer, handler)));
+            }
+        }
+
+        /// Collapses the selection to the cursor if it includes hidden text
+        fn unselect_hidden(&self, buffer: &gtk::TextBuffer) {
+            let Some((start, end)) = buffer.selection_bounds() else {
+                return;
+            };
+
+            let text = buffer.slice(&buffer.start_iter(), &buffer.end_iter(), true);
+            let char_offset = |byte: usize| text[..byte].chars().count() as i32;
+
+            let includes_hidden = self.hidden_ranges(&text).iter().any(|range| {
+                char_offset(range.start) < end.offset() && start.offset() < char_offset(range.end)
+            });
+
+            if includes_hidden {
+                let cursor = buffer.iter_at_mark(&buffer.get_insert());
+                buffer.select_range(&cursor, &cursor);
+            }
+        }
+
+        /// Returns byte ranges of the spoilers that aren't revealed
+        fn hidden_ranges(&self, text: &str) -> Vec<Range<usize>> {
+            let units = self.units.get();
+
+            self.spoilers
+                .borrow()
+                .iter()
+                .filter(|spoiler| !spoiler.is_revealed())
+                .map(|spoiler| byte_range(text, &spoiler.range, units))
+                .collect()
+        }
+
+        /// Returns rects, reveal progress and click point of every spoiler that isn't revealed
+        fn hidden_regions(&self) -> Vec<(Vec<graphene::Rect>, f32, graphene::Point)> {
+            self.spoilers
+                .borrow()
+                .iter()
+                .filter(|spoiler| !spoiler.is_revealed())
+                .map(|spoiler| {
+                    let (x, y) = spoiler.click_point.get();
+                    (
+                        self.range_rects(&spoiler.range),
+                        spoiler.reveal_progress.get(),
+                        graphene::Point::new(x, y),
+                    )
+                })
+                .filter(|(rects, _, _)| !rects.is_empty())
+                .collect()
+        }
+
+        /// Fills the part of the rects that isn't revealed yet
+        fn snapshot_hidden_region(
+            snapshot: &gtk::Snapshot,
+            rects: &[graphene::Rect],
+            progress: f32,
+            center: &graphene::Point,
+        ) {
+            let bounds = rects
+                .iter()
+                .skip(1)
+                .fold(rects[0], |bounds, rect| bounds.union(rect));
+
+            let masked = spoiler_overlay::push_reveal_mask(snapshot, &bounds, center, progress);
+
+            for rect in rects {
+                snapshot.append_color(&gdk::RGBA::BLACK, rect);
+            }
+
+            if masked {
+                snapshot.pop();
+            }
+        }
+
+        /// Returns the center of the first rect of the range
+        pub(super) fn range_center(&self, range: &Range<usize>) -> (f32, f32) {
+            self.range_rects(range)
+                .first()
+                .map(|rect| (rect.center().x(), rect.center().y()))
+                .unwrap_or_default()
+        }
+
+        /// Returns rects of the glyph runs of the range in widget coordinates
+        fn range_rects(&self, range: &Range<usize>) -> Vec<graphene::Rect> {
+            let widget = self.obj();
+
+            if let Some((layout, position)) = &*self.layout.borrow() {
+                return self.layout_rects(layout, range, position);
+            }
+
+            let Some(child) = widget.child() else {
+                return Vec::new();
+            };
+
+            if let Some(label) = child.downcast_ref::<gtk::Label>() {
+                let (x, y) = label.layout_offsets();
+                let Some(position) =
+                    label.compute_point(&*widget, &graphene::Point::new(x as f32, y as f32))
+                else {
+                    return Vec::new();
+                };
+
+                self.layout_rects(&label.layout(), range, &position)
+            } else if let Some(text_view) = child.downcast_ref::<gtk::TextView>() {
+                self.text_view_rects(text_view, range)
+            } else {
+                Vec::new()
+            }
+        }
+
+        fn layout_rects(
+            &self,
+            layout: &pango::Layout,
+            range: &Range<usize>,
+            position: &graphene::Point,
+        ) -> Vec<graphene::Rect> {
+            let text = layout.text();
+            let range = byte_range(&text, range, self.units.get());
+
+            let mut rects = Vec::new();
+            let mut iter = layout.iter();
+
+            loop {
+                let line_start = iter.index() as usize;
+                let (_, logical_rect) = iter.line_extents();
+                let line = iter.line_readonly();
+
+                let has_next = iter.next_line();
+                let line_end = if has_next {
+                    iter.index() as usize
+                } else {
+                    text.len()
+                };
+
+                let start = range.start.max(line_start);
+                let end = range.end.min(line_end);
+
+                if let Some(line) = line.filter(|_| start < end) {
+                    let x_ranges = line.x_ranges(start as i32, end as i32);
+
+                    for x_range in x_ranges.chunks_exact(2) {
+                        rects.push(graphene::Rect::new(
+                            position.x() + pango::units_to_double(x_range[0]) as f32,
+                            position.y() + pango::units_to_double(logical_rect.y()) as f32,
+                            pango::units_to_double(x_range[1] - x_range[0]) as f32,
+                            pango::units_to_double(logical_rect.height()) as f32,
+                        ));
+                    }
+                }
+
+                if !has_next || line_end >= range.end {
+                    break;
+                }
+            }
+
+            rects
+        }
+
+        fn text_view_rects(
+            &self,
+            text_view: &gtk::TextView,
+            range: &Range<usize>,
+        ) -> Vec<graphene::Rect> {
+            let buffer = text_view.buffer();
+            let text = buffer.slice(&buffer.start_iter(), &buffer.end_iter(), true);
+            let range = byte_range(&text, range, self.units.get());
+
+            let char_offset = |byte: usize| text[..byte].chars().count() as i32;
+
+            let mut iter = buffer.iter_at_offset(char_offset(range.start));
+            let end = buffer.iter_at_offset(char_offset(range.end));
+
+            let mut rects = Vec::new();
+
+            while iter < end {
+                let mut line_end = iter;
+                if !text_view.forward_display_line_end(&mut line_end) || line_end > end {
+                    line_end = end;
+                }
+
+                let start_x = text_view.iter_location(&iter).x();
+                let end_x = text_view.iter_location(&line_end).x();
+                let (line_y, line_height) = text_view.line_yrange(&iter);
+
+                // Right-to-left lines end to the left of the start
+                let (x, y) = text_view.buffer_to_window_coords(
+                    gtk::TextWindowType::Widget,
+                    start_x.min(end_x),
+                    line_y,
+                );
+
+                if let Some(position) =
+                    text_view.compute_point(&*self.obj(), &graphene::Point::new(x as f32, y as f32))
+                {
+                    rects.push(graphene::Rect::new(
+                        position.x(),
+                        position.y(),
+                        (end_x - start_x).abs() as f32,
+                        line_height as f32,
+                    ));
+                }
+
+                if !text_view.forward_display_line(&mut iter) {
+                    break;
+                }
+            }
+
+            rects
+        }
+    }
+}
+
+glib::wrapper! {
+    #[doc(alias = "OriTextSpoiler")]
+    /// Telegram-like spoilers for ranges of text
+    ///
+    /// The child should be a [gtk::Label] or a [gtk::TextView],
+    /// glyphs of the ranges aren't drawn and particles are shown over them instead.
+    /// Clicking a range reveals it with the same animation as in [SpoilerOverlay](crate::SpoilerOverlay).
+    ///
+    /// Other widgets that draw a [pango::Layout] can be used with [set_layout](Self::set_layout).
+    ///
+    /// While some text is hidden a label child can't be selected
+    /// and its accessible label has ellipses instead of the hidden ranges.
+    /// Selections of a text view child are cleared when they include hidden text,
+    /// but screen readers still read the whole buffer of the text view.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use gtk::prelude::*;
+    /// let label = gtk::Label::new(Some("The cake is a lie"));
+    /// let spoiler = origami::TextSpoiler::new();
+    /// spoiler.set_child(Some(&label));
+    /// spoiler.set_ranges(&[12..17]);
+    /// ```
+    pub struct TextSpoiler(ObjectSubclass<imp::TextSpoiler>)
+        @extends adw::Bin, gtk::Widget, @implements gtk::Accessible, gtk::Buildable, gtk::ConstraintTarget;
+}
+
+impl TextSpoiler {
+    pub fn new() -> Self {
+        glib::Object::new()
+    }
+
+    /// Hides byte ranges of the text, previous ranges are replaced
+    pub fn set_ranges(&self, ranges: &[Range<usize>]) {
+        self.imp().set_ranges(ranges, RangeUnits::Bytes);
+    }
+
+    /// Hides ranges in UTF-16 code units like offsets of text entities from tdlib
+    pub fn set_utf16_ranges(&self, ranges: &[Range<usize>]) {
+        self.imp().set_ranges(ranges, RangeUnits::Utf16);
+    }
+
+    /// Uses the layout instead of the layout of the child
+    ///
+    /// `x` and `y` is the position where the child draws the layout in widget coordinates.
+    pub fn set_layout(&self, layout: Option<&pango::Layout>, x: f32, y: f32) {
+        self.imp()
+            .layout
+            .replace(layout.map(|layout| (layout.clone(), graphene::Point::new(x, y))));
+        self.queue_draw();
+    }
+
+    /// Reveals all ranges with animation starting from the center of each range
+    pub fn reveal_all(&self) {
+        let imp = self.imp();
+        let count = imp.spoilers.borrow().len();
+
+        for index in 0..count {
+            let center = imp
+                .spoilers
+                .borrow()
+                .get(index)
+                .map(|spoiler| imp.range_center(&spoiler.range));
+
+            if let Some(center) = center {
+                imp.reveal(index, center);
+            }
+        }
+    }
+
+    /// Hides all ranges again
+    pub fn hide_all(&self) {
+        let imp = self.imp();
+
+        for spoiler in imp.spoilers.borrow().iter() {
+            if let Some(animation) = spoiler.animation.take() {
+                animation.pause();
+            }
+            spoiler.reveal_progress.set(0.0);
+        }
+
+        imp.start_ticking();
+        imp.update_concealed_child();
+        self.queue_draw();
+    }
+
+    fn time(&self) -> i64 {
+        self.frame_clock()
+            .and_then(|clk| clk.current_timings())
+            .map(|t| t.frame_time())
+            .unwrap_or_default()
+    }
+}
+
+impl Default for TextSpoiler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Converts the range to bytes, it's clamped to the text and to char boundaries
+fn byte_range(text: &str, range: &Range<usize>, units: RangeUnits) -> Range<usize> {
+    match units {
+        RangeUnits::Bytes => {
+            let floor = |mut index: usize| {
+                index = index.min(text.len());
+                while !text.is_char_boundary(index) {
+                    index -= 1;
+                }
+                index
+            };
+            floor(range.start)..floor(range.end)
+        }
+        RangeUnits::Utf16 => {
+            let mut utf16_offset = 0;
+            let mut start = None;
+            let mut end = text.len();
+
+            for (byte, char) in text.char_indices() {
+                if start.is_none() && utf16_offset >= range.start {
+                    start = Some(byte);
+                }
+                if utf16_offset >= range.end {
+                    end = byte;
+                    break;
+                }
+                utf16_offset += char.len_utf16();
+            }
+
+            start.unwrap_or(text.len())..end
+        }
+    }
+}
+
+/// Returns the text with every byte range replaced by an ellipsis
+///
+/// Overlapping ranges are replaced by a single ellipsis.
+fn concealed_text(text: &str, ranges: &[Range<usize>]) -> String {
+    let mut ranges: Vec<_> = ranges.iter().filter(|range| !range.is_empty()).collect();
+    ranges.sort_by_key(|range| range.start);
+
+    let mut concealed = String::with_capacity(text.len());
+    let mut end = 0;
+
+    for range in ranges {
+        if range.start >= end {
+            concealed.push_str(&text[end..range.start]);
+            concealed.push('…');
+        }
+        end = end.max(range.end);
+    }
+
+    concealed.push_str(&text[end..]);
+    concealed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn utf16_ranges_count_surrogate_pairs() {
+        let text = "a😀b";
+
+        // The emoji is 2 UTF-16 units and 4 bytes
+        assert_eq!(byte_range(text, &(1..3), RangeUnits::Utf16), 1..5);
+        assert_eq!(byte_range(text, &(3..4), RangeUnits::Utf16), 5..6);
+        // Offsets in the middle of the pair round up to the next char
+        assert_eq!(byte_range(text, &(2..4), RangeUnits::Utf16), 5..6);
+    }
+
+    #[test]
+    fn byte_ranges_are_floored_to_char_boundaries() {
+        let text = "a😀b";
+
+        assert_eq!(byte_range(text, &(2..6), RangeUnits::Bytes), 1..6);
+        assert_eq!(byte_range(text, &(0..3), RangeUnits::Bytes), 0..1);
+    }
+
+    #[test]
+    fn ranges_past_the_end_are_clamped() {
+        let text = "spoiler";
+
+        for units in [RangeUnits::Bytes, RangeUnits::Utf16] {
+            assert_eq!(byte_range(text, &(3..100), units), 3..7);
+            assert_eq!(byte_range(text, &(50..100), units), 7..7);
+        }
+
+        assert_eq!(concealed_text(text, &[3..7, 7..7]), "spo…");
+    }
+
+    #[test]
+    fn overlapping_ranges_are_concealed_once() {
+        let text = "The cake is a lie";
+
+        assert_eq!(concealed_text(text, &[4..11, 0..8]), "… a lie");
+        assert_eq!(concealed_text(text, &[14..17, 4..8, 4..6]), "The … is a …");
+    }
+}